@@ -1,11 +1,22 @@
+#[cfg(feature = "cli")]
 use clap::{Arg, ArgAction, Command, command};
+#[cfg(feature = "cli")]
 use flate2::Compression;
+#[cfg(feature = "cli")]
 use flate2::GzBuilder;
+#[cfg(all(feature = "cli", feature = "i18n"))]
 use gettextrs::gettext;
+#[cfg(feature = "cli")]
 use std::io::prelude::*;
 
+#[cfg(all(feature = "cli", not(feature = "i18n")))]
+fn gettext(msgid: &str) -> String {
+    msgid.to_string()
+}
+
 // The below is copied from src/main.rs
 
+#[cfg(feature = "cli")]
 macro_rules! base_app {
     () => {
         command!()
@@ -37,6 +48,13 @@ macro_rules! base_app {
                         .long("audio-device")
                         .help(gettext("Specify the audio device to use"))
                 )
+                .arg(
+                    Arg::new("raw-pcm")
+                        .long("raw-pcm")
+                        .value_name("RATE:CHANNELS:FORMAT")
+                        .conflicts_with_all(["list-devices", "audio-device"])
+                        .help(gettext("Read raw PCM samples from the standard input instead of using mic input, e.g. 44100:2:s16le (formats: u8, s16le, s16be, s24le, s32le, f32le)"))
+                )
                 .arg(
                     Arg::new("request-interval")
                         .short('i')
@@ -45,6 +63,75 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -60,6 +147,19 @@ macro_rules! base_app {
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("lyrics")
+                        .long("lyrics")
+                        .conflicts_with_all(["json", "csv"])
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the lyrics of recognized songs after their names, when Shazam knows them"))
+                )
+                .arg(
+                    Arg::new("detect-changes")
+                        .long("detect-changes")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Recognize only when music starts or changes, instead of at a fixed interval"))
+                )
                 .arg(
                     Arg::new("disable-mpris")
                         .long("disable-mpris")
@@ -84,6 +184,13 @@ macro_rules! base_app {
                         .action(ArgAction::Set)
                         .help(gettext("Specify the audio device to use"))
                 )
+                .arg(
+                    Arg::new("raw-pcm")
+                        .long("raw-pcm")
+                        .value_name("RATE:CHANNELS:FORMAT")
+                        .conflicts_with_all(["list-devices", "audio-device", "input_file"])
+                        .help(gettext("Read raw PCM samples from the standard input instead of using mic input, e.g. 44100:2:s16le (formats: u8, s16le, s16be, s24le, s32le, f32le)"))
+                )
                 .arg(
                     Arg::new("request-interval")
                         .short('i')
@@ -92,6 +199,75 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -107,6 +283,37 @@ macro_rules! base_app {
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("lyrics")
+                        .long("lyrics")
+                        .conflicts_with_all(["json", "csv"])
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the lyrics of recognized songs after their names, when Shazam knows them"))
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .requires("input_file")
+                        .value_parser(clap::value_parser!(u64))
+                        .conflicts_with("strategy")
+                        .help(gettext("Position of the excerpt of the file to recognize, in seconds"))
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .requires("input_file")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(1..=12))
+                        .help(gettext("Length of the excerpt of the file to recognize, in seconds (at most 12)"))
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .long("strategy")
+                        .requires("input_file")
+                        .default_value("middle")
+                        .value_parser(["middle", "start", "end", "multi"])
+                        .help(gettext("Take the excerpt of the file to recognize from its middle, start or end, or try several excerpts in turn until a match is found (multi)"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(false)
@@ -146,10 +353,100 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
         )
         .subcommand(
             Command::new("audio-file-to-fingerprint")
                 .about(gettext("Generate a Shazam fingerprint from a sound file, and print it to the standard output."))
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .value_parser(clap::value_parser!(u64))
+                        .conflicts_with("strategy")
+                        .help(gettext("Position of the excerpt of the file to recognize, in seconds"))
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(1..=12))
+                        .help(gettext("Length of the excerpt of the file to recognize, in seconds (at most 12)"))
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .long("strategy")
+                        .default_value("middle")
+                        .value_parser(["middle", "start", "end", "multi"])
+                        .help(gettext("Take the excerpt of the file to recognize from its middle, start or end, or try several excerpts in turn until a match is found (multi)"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(true)
@@ -165,6 +462,267 @@ macro_rules! base_app {
                         .help(gettext("The data-URI Shazam fingerprint to recognize."))
                 )
         )
+        .subcommand(
+            Command::new("convert-fingerprint")
+                .about(gettext("Convert a Shazam fingerprint between the data-URI, JSON and TOML formats, and print it to the standard output."))
+                .arg(
+                    Arg::new("to")
+                        .short('t')
+                        .long("to")
+                        .default_value("json")
+                        .value_parser(["uri", "json", "toml"])
+                        .help(gettext("Output format"))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint, or file containing a binary, data-URI, JSON or TOML fingerprint, to convert."))
+                )
+        )
+        .subcommand(
+            Command::new("inspect-fingerprint")
+                .about(gettext("Print the header fields, checksum validity and frequency peaks of a Shazam fingerprint, for debugging purposes."))
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .default_value("text")
+                        .value_parser(["text", "json", "csv"])
+                        .help(gettext("Output format: a readable report, the full report in JSON, or the frequency peaks in CSV"))
+                )
+                .arg(
+                    Arg::new("plot")
+                        .short('p')
+                        .long("plot")
+                        .help(gettext("Render the frequency peaks as a constellation plot to this PNG file"))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint, or binary fingerprint file, to inspect."))
+                )
+        )
+        .subcommand(
+            Command::new("slice-fingerprint")
+                .about(gettext("Cut a data-URI Shazam fingerprint to a time range, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("start")
+                        .short('s')
+                        .long("start")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("Start of the range to keep, in seconds"))
+                )
+                .arg(
+                    Arg::new("end")
+                        .short('e')
+                        .long("end")
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("End of the range to keep, in seconds (defaults to the end of the fingerprint)"))
+                )
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to cut."))
+                )
+        )
+        .subcommand(
+            Command::new("shift-fingerprint")
+                .about(gettext("Shift the contents of a data-URI Shazam fingerprint in time, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("offset")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("The offset to apply in seconds: a positive value inserts silence at the start, a negative value cuts the start."))
+                )
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to shift."))
+                )
+        )
+        .subcommand(
+            Command::new("trim-fingerprint")
+                .about(gettext("Remove the silent start and end of a data-URI Shazam fingerprint, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to trim."))
+                )
+        )
+        .subcommand(
+            Command::new("concatenate-fingerprints")
+                .about(gettext("Join data-URI Shazam fingerprints one after the other, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("fingerprints")
+                        .required(true)
+                        .num_args(2..)
+                        .help(gettext("The data-URI Shazam fingerprints to join, in order."))
+                )
+        )
+        .subcommand(
+            Command::new("compare-fingerprints")
+                .about(gettext("Compare two sound files or data-URI Shazam fingerprints offline, and print in JSON their similarity score, number of matching frequency peaks and time offset."))
+                .arg(
+                    Arg::new("frequency-tolerance")
+                        .short('t')
+                        .long("frequency-tolerance")
+                        .value_parser(clap::value_parser!(u32))
+                        .help(gettext("Maximal frequency difference between two matching peaks, in FFT bins of about 7.8 Hz (defaults to 1)"))
+                )
+                .arg(
+                    Arg::new("first_input")
+                        .required(true)
+                        .help(gettext("The first audio file or data-URI Shazam fingerprint to compare."))
+                )
+                .arg(
+                    Arg::new("second_input")
+                        .required(true)
+                        .help(gettext("The second audio file or data-URI Shazam fingerprint to compare."))
+                )
+        )
+        .subcommand(
+            Command::new("build-local-database")
+                .about(gettext("Fingerprint audio files and add them to a local database, allowing to recognize them later without network access."))
+                .arg(
+                    Arg::new("database")
+                        .required(true)
+                        .help(gettext("The local database file to create or update."))
+                )
+                .arg(
+                    Arg::new("input_paths")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files or directories to index."))
+                )
+        )
+        .subcommand(
+            Command::new("recognize-from-local-database")
+                .about(gettext("Recognize a sound file or a data-URI Shazam fingerprint against a local database, and print the matching songs in JSON to the standard output."))
+                .arg(
+                    Arg::new("database")
+                        .required(true)
+                        .help(gettext("The local database file to use."))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The audio file or data-URI Shazam fingerprint to recognize."))
+                )
+        )
+        .subcommand(
+            Command::new("tracklist")
+                .about(gettext("Recognize songs all along a long sound file (e.g. a DJ mix or a radio recording), and print a timestamped tracklist."))
+                .arg(
+                    Arg::new("step")
+                        .short('s')
+                        .long("step")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u32))
+                        .help(gettext("Interval between the starts of two recognized excerpts of the file, in seconds"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in JSON"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in the CSV format"))
+                )
+                .arg(
+                    Arg::new("input_file")
+                        .required(true)
+                        .help(gettext("The audio file to generate a tracklist for."))
+                )
+        )
+        .subcommand(
+            Command::new("serve-fixtures")
+                .about(gettext("Run a local server imitating Shazam's API, which answers with responses recorded in JSON files (use with --endpoint or $SONGREC_RECOGNITION_ENDPOINT)."))
+                .arg(
+                    Arg::new("port")
+                        .short('p')
+                        .long("port")
+                        .default_value("8080")
+                        .value_parser(clap::value_parser!(u32).range(1..=65535))
+                        .help(gettext("The local port to listen on"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .required(true)
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn (cover images are served from the files at their URL paths within it)"))
+                )
+        )
     };
 }
 
@@ -224,7 +782,7 @@ macro_rules! app {
     };
 }
 
-#[cfg(not(feature = "gui"))]
+#[cfg(all(feature = "cli", not(feature = "gui")))]
 macro_rules! app {
     () => {
         base_app!()
@@ -240,7 +798,7 @@ fn main() {
     // Regenerate .po, .mo, .pot translation
     // files from the source tree
 
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "i18n"))]
     if !std::process::Command::new("./update_po_files.sh")
         .current_dir("translations")
         .status()
@@ -257,7 +815,7 @@ troubleshoot the error"
     // Regenerate GTK Builder .ui files from
     // GNOME Builder .blp files
 
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "gui"))]
     if let Ok(output) = std::process::Command::new("blueprint-compiler")
         .current_dir("src/gui")
         .args([
@@ -277,7 +835,7 @@ troubleshoot the error"
         }
     }
 
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "gui"))]
     if let Ok(output) = std::process::Command::new("blueprint-compiler")
         .current_dir("src/gui")
         .args([
@@ -302,12 +860,19 @@ troubleshoot the error"
 
     // Generate GLib resources
 
+    #[cfg(feature = "gui")]
     glib_build_tools::compile_resources(
         &["src/gui"],
         "src/gui/resources.gresource.xml",
         "compiled.gresource",
     );
 
+    #[cfg(feature = "cli")]
+    generate_manpages();
+}
+
+#[cfg(feature = "cli")]
+fn generate_manpages() {
     // Generate manpages
 
     let temp_out_dir = tempfile::tempdir().unwrap();
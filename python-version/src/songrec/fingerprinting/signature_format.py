@@ -250,6 +250,42 @@ class DecodedMessage:
             },
         }
 
+    """
+        Decode the JSON format produced by "encode_to_json" (or by the
+        "convert-fingerprint" subcommand of the Rust version). Fields
+        starting with an underscore are informational and ignored.
+    """
+
+    @classmethod
+    def decode_from_json(cls, json_object: dict):
+
+        self = cls()
+
+        self.sample_rate_hz = int(json_object['sample_rate_hz'])
+        self.number_samples = int(json_object['number_samples'])
+
+        self.frequency_band_to_sound_peaks = {}
+
+        for frequency_band_name, frequency_peaks in json_object[
+            'frequency_band_to_peaks'
+        ].items():
+            if not frequency_peaks:
+                continue
+
+            frequency_band = FrequencyBand['_' + frequency_band_name]
+
+            self.frequency_band_to_sound_peaks[frequency_band] = [
+                FrequencyPeak(
+                    frequency_peak['fft_pass_number'],
+                    frequency_peak['peak_magnitude'],
+                    frequency_peak['corrected_peak_frequency_bin'],
+                    self.sample_rate_hz,
+                )
+                for frequency_peak in frequency_peaks
+            ]
+
+        return self
+
     def encode_to_binary(self) -> bytes:
 
         header = RawSignatureHeader()
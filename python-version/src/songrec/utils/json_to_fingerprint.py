@@ -0,0 +1,49 @@
+#!/usr/bin/env python3
+# -*- encoding: Utf-8 -*-
+from os.path import dirname, realpath
+from argparse import ArgumentParser
+from json import load
+
+SCRIPT_DIR = dirname(realpath(__file__))
+MODULE_DIR = dirname(realpath(SCRIPT_DIR))
+SRC_DIR = dirname(realpath(MODULE_DIR))
+
+import sys
+
+sys.path.append(SRC_DIR)
+
+from songrec.fingerprinting.signature_format import DecodedMessage
+
+"""
+    Sample usage: ./json_to_fingerprint.py fingerprint.json
+"""
+
+
+def main():
+    args = ArgumentParser(
+        description='Convert a JSON Shazam fingerprint, as produced by '
+        + 'fingerprint_to_json.py, into a data-URI fingerprint written '
+        + 'to the standard output.'
+    )
+
+    args.add_argument(
+        'json_file',
+        help='The JSON file to read the fingerprint from '
+        + '("-" for the standard input).',
+    )
+
+    args = args.parse_args()
+
+    if args.json_file == '-':
+        json_object = load(sys.stdin)
+    else:
+        with open(args.json_file) as json_file:
+            json_object = load(json_file)
+
+    message = DecodedMessage.decode_from_json(json_object)
+
+    print(message.encode_to_uri())
+
+
+if __name__ == '__main__':
+    main()
@@ -2,15 +2,18 @@ use log::{error, info, warn};
 use std::error::Error;
 use std::sync::{Arc, Mutex};
 
+use crate::utils::internationalization::gettext;
 use chrono::Local;
-use gettextrs::gettext;
 
 #[cfg(all(target_os = "linux", feature = "mpris"))]
 use mpris_server::PlaybackStatus;
 
+use crate::core::fingerprinting::communication::cover_endpoint;
+use crate::core::fingerprinting::recognition_window::RecognitionWindow;
 use crate::core::http_task::http_task;
-use crate::core::microphone_thread::microphone_thread;
+use crate::core::microphone_thread::{RawPcmFormat, microphone_thread, raw_pcm_thread};
 use crate::core::processing_thread::processing_thread;
+use crate::core::recognition_backends::recognition_backend::get_recognition_backend;
 use crate::core::thread_messages::{
     GUIMessage, MicrophoneMessage, ProcessingMessage, spawn_big_thread,
 };
@@ -20,6 +23,7 @@ use crate::core::preferences::{Preferences, PreferencesInterface};
 use crate::plugins::mpris_player::{get_player, update_song};
 use crate::utils::csv_song_history::SongHistoryRecord;
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum CLIOutputType {
     SongName,
     JSON,
@@ -32,8 +36,22 @@ pub struct CLIParameters {
     pub recognize_once: bool,
     pub audio_device: Option<String>,
     pub request_interval: u64,
+    pub min_quality_percent: u64,
+    pub detect_song_changes: bool,
+    pub recognition_backend: String,
+    pub recognition_fixtures_path: Option<String>,
+    pub recognition_command: Option<String>,
+    pub recognition_endpoint: Option<String>,
+    pub cover_endpoint: Option<String>,
+    pub request_locale: Option<String>,
+    pub request_timezone: Option<String>,
+    pub request_latitude: Option<f64>,
+    pub request_longitude: Option<f64>,
     pub input_file: Option<String>,
+    pub recognition_window: RecognitionWindow,
+    pub raw_pcm_format: Option<RawPcmFormat>,
     pub output_type: CLIOutputType,
+    pub print_lyrics: bool,
 }
 
 pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
@@ -48,27 +66,64 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
     let microphone_tx_2 = microphone_tx.clone();
     let microphone_tx_3 = microphone_tx.clone();
 
+    let mut preferences = Preferences::with_interval(parameters.request_interval);
+    preferences.min_quality_percent = Some(parameters.min_quality_percent);
+    preferences.detect_song_changes = Some(parameters.detect_song_changes);
+    preferences.recognition_backend = Some(parameters.recognition_backend.clone());
+    preferences.recognition_fixtures_path = parameters.recognition_fixtures_path.clone();
+    preferences.recognition_command = parameters.recognition_command.clone();
+    preferences.recognition_endpoint = parameters.recognition_endpoint.clone();
+    preferences.request_locale = parameters.request_locale.clone();
+    preferences.request_timezone = parameters.request_timezone.clone();
+    preferences.request_latitude = parameters.request_latitude;
+    preferences.request_longitude = parameters.request_longitude;
+
+    let recognition_backend = get_recognition_backend(&preferences)?;
+
     let preferences_interface = Arc::new(Mutex::new(PreferencesInterface {
         preferences_file_path: None,
-        preferences: Preferences::with_interval(parameters.request_interval),
+        preferences,
     }));
 
+    // Read raw PCM samples from the standard input rather than from an
+    // audio device if requested
+
+    let raw_pcm_format = parameters.raw_pcm_format;
+    let preferences_interface_2 = preferences_interface.clone();
+
     spawn_big_thread(move || {
-        microphone_thread(
-            microphone_rx,
-            microphone_tx_2,
-            processing_tx_2,
-            gui_tx_2,
-            preferences_interface,
-            true,
-        );
+        if let Some(raw_pcm_format) = raw_pcm_format {
+            raw_pcm_thread(
+                microphone_rx,
+                processing_tx_2,
+                gui_tx_2,
+                preferences_interface,
+                raw_pcm_format,
+            );
+        } else {
+            microphone_thread(
+                microphone_rx,
+                microphone_tx_2,
+                processing_tx_2,
+                gui_tx_2,
+                preferences_interface,
+                true,
+            );
+        }
     });
 
     spawn_big_thread(move || {
         processing_thread(processing_rx, http_tx, gui_tx_3);
     });
 
-    glib::spawn_future_local(http_task(http_rx, gui_tx, microphone_tx_3));
+    glib::spawn_future_local(http_task(
+        http_rx,
+        gui_tx,
+        microphone_tx_3,
+        preferences_interface_2,
+        recognition_backend,
+        cover_endpoint(parameters.cover_endpoint.as_deref()),
+    ));
 
     let main_loop = glib::MainLoop::new(None, false);
     let loop_inner = main_loop.clone();
@@ -93,13 +148,17 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
         let mut last_cover_path = None;
 
         let mut last_track: Option<String> = None;
+        let mut in_retry_cooldown = false;
 
         let audio_dev_name = parameters.audio_device.as_ref().map(|dev| dev.to_string());
         let input_file_name = parameters.input_file.as_ref().map(|dev| dev.to_string());
 
         if let Some(ref filename) = parameters.input_file {
             processing_tx
-                .try_send(ProcessingMessage::ProcessAudioFile(filename.to_string()))
+                .try_send(ProcessingMessage::ProcessAudioFile(
+                    filename.to_string(),
+                    parameters.recognition_window,
+                ))
                 .unwrap();
         }
 
@@ -175,6 +234,19 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                         }
                     }
                 }
+                GUIMessage::RetryCooldown(remaining) => {
+                    // Only report the start of each cooldown
+                    if remaining.is_zero() {
+                        in_retry_cooldown = false;
+                    } else if !in_retry_cooldown {
+                        in_retry_cooldown = true;
+                        warn!(
+                            "{}",
+                            gettext("Retrying in %s seconds")
+                                .replace("%s", &remaining.as_secs_f32().ceil().to_string())
+                        );
+                    }
+                }
                 GUIMessage::ErrorMessage(string) => {
                     if string != gettext("No match for this song") || input_file_name.is_some() {
                         error!("{} {}", gettext("Error:"), string);
@@ -183,9 +255,22 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                         break;
                     }
                 }
+                GUIMessage::SignatureSkipped(quality) => {
+                    info!(
+                        "{}",
+                        gettext(
+                            "Not sending a recording with too little music in it (quality: %s%)"
+                        )
+                        .replace("%s", &format!("{:.0}", quality.score * 100.0))
+                    );
+                }
                 GUIMessage::MicrophoneRecording if !do_recognize_once => {
                     info!("{}", gettext("Recording started!"));
                 }
+                GUIMessage::QuitApplication => {
+                    // The raw PCM input reached its end
+                    break;
+                }
                 GUIMessage::SongRecognized(message) => {
                     let track_key = Some(message.track_key.clone());
                     let song_name = format!("{} - {}", message.artist_name, message.song_name);
@@ -200,25 +285,68 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                         last_track = track_key;
                         match parameters.output_type {
                             CLIOutputType::JSON => {
-                                println!("{}", message.shazam_json);
+                                // Add how the recording matched the song and
+                                // where to find it to the response of Shazam
+
+                                let mut json_object: serde_json::Value =
+                                    serde_json::from_str(&message.shazam_json).unwrap();
+
+                                match json_object.as_object_mut() {
+                                    Some(json_map) => {
+                                        json_map.insert(
+                                            "match_details".to_string(),
+                                            serde_json::to_value(message.match_details).unwrap(),
+                                        );
+                                        json_map.insert(
+                                            "streaming_links".to_string(),
+                                            serde_json::to_value(&message.streaming_links).unwrap(),
+                                        );
+                                        println!("{}", json_object);
+                                    }
+                                    // The command and fixtures backends are
+                                    // not trusted to return an object
+                                    None => error!(
+                                        "{} {}",
+                                        gettext("Error:"),
+                                        gettext(
+                                            "The response of the recognition backend is not a JSON object"
+                                        )
+                                    ),
+                                }
                             }
                             CLIOutputType::CSV => {
                                 csv_writer
-                                    .serialize(SongHistoryRecord {
-                                        song_name,
-                                        album: Some(message.album_name.unwrap_or_default()),
-                                        track_key: Some(message.track_key),
-                                        release_year: Some(
-                                            message.release_year.unwrap_or_default(),
+                                    .serialize(
+                                        SongHistoryRecord {
+                                            song_name,
+                                            album: Some(message.album_name.unwrap_or_default()),
+                                            track_key: Some(message.track_key),
+                                            release_year: Some(
+                                                message.release_year.unwrap_or_default(),
+                                            ),
+                                            genre: Some(message.genre.unwrap_or_default()),
+                                            recognition_date: Local::now().format("%c").to_string(),
+                                            ..Default::default()
+                                        }
+                                        .with_match_details(message.match_details)
+                                        .with_lyrics(message.lyrics.as_deref())
+                                        .with_streaming_links(
+                                            message.isrc,
+                                            message.streaming_links,
                                         ),
-                                        genre: Some(message.genre.unwrap_or_default()),
-                                        recognition_date: Local::now().format("%c").to_string(),
-                                    })
+                                    )
                                     .unwrap();
                                 csv_writer.flush().unwrap();
                             }
                             CLIOutputType::SongName => {
                                 println!("{}", song_name);
+
+                                if parameters.print_lyrics {
+                                    match message.lyrics {
+                                        Some(lyrics) => println!("\n{}\n", lyrics.join("\n")),
+                                        None => info!("{}", gettext("No lyrics for this song")),
+                                    }
+                                }
                             }
                         };
                     }
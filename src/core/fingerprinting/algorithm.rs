@@ -1,20 +1,61 @@
-use realfft::RealFftPlanner;
+use log::debug;
+use realfft::{RealFftPlanner, RealToComplex};
+use rodio::Source;
 use rodio::conversions::SampleTypeConverter;
 use rodio::nz;
+use rodio::source::UniformSourceIterator;
 use rustfft::num_complex::Complex;
 use rustfft::num_traits::Zero;
 use std::error::Error;
-use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::core::fingerprinting::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
+use crate::core::fingerprinting::recognition_window::RecognitionWindow;
 use crate::core::fingerprinting::signature_format::{
     DecodedSignature, FrequencyBand, FrequencyPeak,
 };
 #[cfg(feature = "ffmpeg")]
 use crate::plugins::ffmpeg_wrapper::decode_with_ffmpeg;
+#[cfg(feature = "opus")]
+use crate::plugins::opus_decoder::decode_with_opus;
+
+/// The 16 KHz mono samples of an audio file being decoded. When reading
+/// from the standard input, this keeps its temporary copy alive until the
+/// decoder is done with it, as FFMpeg may need to reopen it for seeking.
+pub struct DecodedFileSamples {
+    // Declared first so that the decoder is dropped before the file
+    samples: UniformSourceIterator<Box<dyn Source + Send>>,
+    _stdin_file: Option<tempfile::NamedTempFile>,
+}
+
+impl Iterator for DecodedFileSamples {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.samples.next()
+    }
 
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+/// Generates a Shazam signature from 16 KHz mono samples.
+///
+/// The generator can be kept alive across calls: samples are passed
+/// incrementally through `feed()`, the signature computed so far can be
+/// obtained at any moment through `snapshot()` (or for the last samples
+/// only through `snapshot_last()`, e.g. for a sliding window), and
+/// `reset()` allows to start over without allocating buffers or planning
+/// the FFT again.
 pub struct SignatureGenerator {
     // Used when processing input:
+    /// Samples passed to `feed()` that do not fill a whole 128-sample
+    /// chunk yet.
+    pending_samples: Vec<i16>,
+
     /// Ring buffer.
     ring_buffer_of_samples: Box<[i16; 2048]>,
     ring_buffer_of_samples_index: usize,
@@ -32,7 +73,7 @@ pub struct SignatureGenerator {
     fft_outputs: Box<[[f32; 1025]; 256]>,
     fft_outputs_index: u8,
 
-    fft_object: RealFftPlanner<f32>,
+    fft_object: Arc<dyn RealToComplex<f32>>,
 
     /// Ring buffer.
     spread_fft_outputs: Box<[[f32; 1025]; 256]>,
@@ -44,64 +85,189 @@ pub struct SignatureGenerator {
 }
 
 impl SignatureGenerator {
-    pub fn make_signature_from_file(file_path: &str) -> Result<DecodedSignature, Box<dyn Error>> {
-        // Decode the .WAV, .MP3, .OGG or .FLAC file
+    /// Open a .WAV, .MP3, .OGG, .FLAC, .M4A or .OPUS/.WEBM file with Rodio
+    /// or the native Opus decoder, without resampling it. Formats are
+    /// detected from the contents of the file rather than from its
+    /// extension.
+    fn open_native_decoder(file_path: &Path) -> Result<Box<dyn Source + Send>, Box<dyn Error>> {
+        // Opening the file itself (rather than a reader) lets Rodio know its
+        // size, which it needs for seeking within it
+
+        let decoder: Result<Box<dyn Source + Send>, Box<dyn Error>> =
+            match rodio::Decoder::try_from(std::fs::File::open(file_path)?) {
+                Ok(decoder) => Ok(Box::new(decoder)),
+                Err(decoding_error) => Err(Box::new(decoding_error)),
+            };
+
+        // Rodio has no Opus decoder, so decode Opus natively in case of
+        // failure with Rodio
+
+        #[cfg(feature = "opus")]
+        let decoder = decoder.or_else(|decoding_error| match decode_with_opus(file_path) {
+            Some(new_decoder) => Ok(Box::new(new_decoder) as Box<dyn Source + Send>),
+            None => Err(decoding_error),
+        });
+
+        decoder
+    }
 
-        #[cfg(not(feature = "ffmpeg"))]
-        let decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(file_path)?));
+    /// Open an audio file (see `open_native_decoder()`), or any format
+    /// supported by FFMpeg if available, without resampling it, and
+    /// position it at `start`. Decoders seek directly to `start` when the
+    /// format allows it (e.g. .WAV, .FLAC, .OGG or .M4A files), and
+    /// otherwise decode and drop the preceding samples on the fly. FFMpeg
+    /// is started at `start` and asked not to decode more than
+    /// `_max_duration` of audio, if provided.
+    pub(crate) fn open_decoder(
+        file_path: &Path,
+        start: Duration,
+        _max_duration: Option<Duration>,
+    ) -> Result<Box<dyn Source + Send>, Box<dyn Error>> {
+        let decoder = SignatureGenerator::open_native_decoder(file_path).and_then(|mut decoder| {
+            if !start.is_zero()
+                && let Err(seek_error) = decoder.try_seek(start)
+            {
+                debug!(
+                    "Could not seek within {}, skipping samples instead: {}",
+                    file_path.display(),
+                    seek_error
+                );
 
-        #[cfg(feature = "ffmpeg")]
-        let decoder = {
-            let mut decoder = rodio::Decoder::new(BufReader::new(std::fs::File::open(file_path)?));
+                // The position of the decoder is unspecified after a failed
+                // seek, unless seeking is not supported at all
 
-            if let Err(ref _decoding_error) = decoder &&
-                // Try to decode with FFMpeg, if available, in case of failure with
-                // Rodio (most likely due to the use of a format unsupported by
-                // Rodio, such as .WMA or .MP4/.AAC)
+                if !seek_error.source_intact() {
+                    decoder = SignatureGenerator::open_native_decoder(file_path)?;
+                }
 
-                let Some(new_decoder) = decode_with_ffmpeg(file_path)
-            {
-                decoder = Ok(new_decoder);
+                return Ok(Box::new(decoder.skip_duration(start)) as Box<dyn Source + Send>);
+            }
+
+            Ok(decoder)
+        });
+
+        // Try to decode with FFMpeg, if available, in case of failure with
+        // Rodio (most likely due to the use of a format unsupported by
+        // Rodio, such as .WMA)
+
+        #[cfg(feature = "ffmpeg")]
+        let decoder = decoder.or_else(|decoding_error| {
+            match decode_with_ffmpeg(file_path, start, _max_duration) {
+                Some(new_decoder) => Ok(Box::new(new_decoder?) as Box<dyn Source + Send>),
+                None => Err(decoding_error),
             }
+        });
 
-            decoder
-        };
+        decoder
+    }
+
+    /// Copy the standard input to a temporary file if `file_path` is "-",
+    /// so that it can be probed and decoded (possibly several times) like
+    /// a regular file. The temporary file is deleted once dropped.
+    pub(crate) fn copy_stdin_if_needed(
+        file_path: &str,
+    ) -> Result<Option<tempfile::NamedTempFile>, Box<dyn Error>> {
+        if file_path == "-" {
+            let mut stdin_file = tempfile::NamedTempFile::new()?;
+            std::io::copy(&mut std::io::stdin().lock(), &mut stdin_file)?;
+            Ok(Some(stdin_file))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decode an audio file (see `open_decoder()`) and downsample it to
+    /// 16 KHz mono. A path of "-" designates the standard input.
+    pub fn decode_file(file_path: &str) -> Result<DecodedFileSamples, Box<dyn Error>> {
+        let stdin_file = SignatureGenerator::copy_stdin_if_needed(file_path)?;
+        let path = stdin_file
+            .as_ref()
+            .map_or(Path::new(file_path), |stdin_file| stdin_file.path());
 
         // Downsample the raw PCM samples to 16 KHz
 
-        let converted_file =
-            rodio::source::UniformSourceIterator::new(decoder?, nz!(1), nz!(16000));
+        Ok(DecodedFileSamples {
+            samples: UniformSourceIterator::new(
+                SignatureGenerator::open_decoder(path, Duration::ZERO, None)?,
+                nz!(1),
+                nz!(16000),
+            ),
+            _stdin_file: stdin_file,
+        })
+    }
+
+    /// Obtain the duration of an audio file from its headers, when the
+    /// format provides it (which is not the case of .MP3 files, for
+    /// example).
+    pub fn file_duration(file_path: &Path) -> Result<Option<Duration>, Box<dyn Error>> {
+        Ok(SignatureGenerator::open_decoder(file_path, Duration::ZERO, None)?.total_duration())
+    }
 
-        let mut raw_pcm_samples: Vec<f32> = converted_file.collect();
+    /// Decode at most `number_samples` 16 KHz mono samples of an audio
+    /// file, starting at `start` (see `open_decoder()`), so that only the
+    /// requested window is ever kept in memory.
+    pub fn decode_file_window(
+        file_path: &Path,
+        start: Duration,
+        number_samples: usize,
+    ) -> Result<Vec<f32>, Box<dyn Error>> {
+        let max_duration = Some(Duration::from_secs_f64(number_samples as f64 / 16000.0));
+
+        let decoder = SignatureGenerator::open_decoder(file_path, start, max_duration)?;
+
+        Ok(UniformSourceIterator::new(decoder, nz!(1), nz!(16000))
+            .take(number_samples)
+            .collect())
+    }
 
-        // Pad the input to at least 12 seconds in order to avoid missing data
-        // at the end of the input
+    /// Generate a signature for 12 seconds taken from the middle of an
+    /// audio file, which increases recognition odds.
+    pub fn make_signature_from_file(file_path: &str) -> Result<DecodedSignature, Box<dyn Error>> {
+        Ok(
+            SignatureGenerator::make_signatures_from_file(
+                file_path,
+                &RecognitionWindow::default(),
+            )?
+            .remove(0),
+        )
+    }
 
-        if raw_pcm_samples.len() < 12 * 16000 {
-            raw_pcm_samples.resize(12 * 16000, 0.0);
-        }
+    pub fn make_signature_from_buffer(f32_mono_16khz_buffer: &[f32]) -> DecodedSignature {
+        let mut this = SignatureGenerator::new();
+
+        this.feed(f32_mono_16khz_buffer);
+
+        this.signature
+    }
 
-        // Skip to the middle of the file in order to increase recognition
-        // odds. Take 12 seconds of sample.
+    /// Generate a signature for the whole contents of an audio file, rather
+    /// than for 12 seconds taken from its middle (e.g. for indexing it).
+    pub fn make_full_signature_from_file(
+        file_path: &str,
+    ) -> Result<DecodedSignature, Box<dyn Error>> {
+        let mut raw_pcm_samples = SignatureGenerator::decode_file(file_path)?;
 
-        let mut raw_pcm_samples_slice: &[f32] = &raw_pcm_samples;
+        let mut this = SignatureGenerator::new();
+        let mut chunk: Vec<f32> = Vec::with_capacity(16000);
 
-        let slice_len = raw_pcm_samples_slice.len().min(12 * 16000);
+        loop {
+            chunk.clear();
+            chunk.extend(raw_pcm_samples.by_ref().take(16000));
 
-        if raw_pcm_samples_slice.len() > 12 * 16000 {
-            let middle = raw_pcm_samples.len() / 2;
+            if chunk.is_empty() {
+                break;
+            }
 
-            raw_pcm_samples_slice =
-                &raw_pcm_samples_slice[middle - (6 * 16000)..middle + (6 * 16000)];
+            this.feed(&chunk);
         }
 
-        Ok(SignatureGenerator::make_signature_from_buffer(
-            &raw_pcm_samples_slice[..slice_len],
-        ))
+        Ok(this.signature)
     }
 
-    pub fn make_signature_from_buffer(f32_mono_16khz_buffer: &[f32]) -> DecodedSignature {
-        let mut this = SignatureGenerator {
+    pub fn new() -> Self {
+        SignatureGenerator {
+            pending_samples: Vec::with_capacity(128),
+
             ring_buffer_of_samples: Box::new([0i16; 2048]),
             ring_buffer_of_samples_index: 0,
 
@@ -111,7 +277,7 @@ impl SignatureGenerator {
             fft_outputs: Box::new([[0.0f32; 1025]; 256]),
             fft_outputs_index: 0u8,
 
-            fft_object: RealFftPlanner::<f32>::new(),
+            fft_object: RealFftPlanner::<f32>::new().plan_fft_forward(2048),
 
             spread_fft_outputs: Box::new([[0.0f32; 1025]; 256]),
             spread_fft_outputs_index: 0u8,
@@ -120,27 +286,126 @@ impl SignatureGenerator {
 
             signature: DecodedSignature {
                 sample_rate_hz: 16000,
-                number_samples: f32_mono_16khz_buffer.len() as u32,
+                number_samples: 0,
                 frequency_band_to_sound_peaks: Default::default(),
             },
-        };
+        }
+    }
 
-        let s16_buffer: Vec<i16> =
-            SampleTypeConverter::<_, i16>::new(f32_mono_16khz_buffer.iter().copied()).collect();
+    /// Process more 16 KHz mono samples, following the ones that were
+    /// previously fed. Samples that do not fill a whole 128-sample FFT
+    /// chunk are kept until the next call.
+    pub fn feed(&mut self, f32_mono_16khz_buffer: &[f32]) {
+        self.signature.number_samples += f32_mono_16khz_buffer.len() as u32;
 
-        for chunk in s16_buffer.as_chunks::<128>().0 {
-            this.do_fft(chunk);
+        self.pending_samples
+            .extend(SampleTypeConverter::<_, i16>::new(
+                f32_mono_16khz_buffer.iter().copied(),
+            ));
 
-            this.do_peak_spreading();
+        let pending_samples = std::mem::take(&mut self.pending_samples);
 
-            this.num_spread_ffts_done += 1;
+        let (chunks, remainder) = pending_samples.as_chunks::<128>();
 
-            if this.num_spread_ffts_done >= 46 {
-                this.do_peak_recognition();
+        for chunk in chunks {
+            self.do_fft(chunk);
+
+            self.do_peak_spreading();
+
+            self.num_spread_ffts_done += 1;
+
+            if self.num_spread_ffts_done >= 46 {
+                self.do_peak_recognition();
             }
         }
 
-        this.signature
+        self.pending_samples = remainder.to_vec();
+    }
+
+    /// Obtain a copy of the signature built from the samples fed since
+    /// the creation of the generator or the last call to `reset()`.
+    pub fn snapshot(&self) -> DecodedSignature {
+        self.signature.clone()
+    }
+
+    /// Obtain a copy of the signature of the last `number_samples` samples
+    /// fed (or of all of them, if fewer were fed), as if they had been fed
+    /// to a new generator, except that peaks are found from their very
+    /// start.
+    pub fn snapshot_last(&self, number_samples: u32) -> DecodedSignature {
+        let number_samples = number_samples.min(self.signature.number_samples);
+        let first_pass = (self.signature.number_samples - number_samples) / 128;
+
+        DecodedSignature {
+            sample_rate_hz: self.signature.sample_rate_hz,
+            number_samples,
+            frequency_band_to_sound_peaks: self
+                .signature
+                .frequency_band_to_sound_peaks
+                .each_ref()
+                .map(|frequency_peaks| {
+                    frequency_peaks
+                        .iter()
+                        .filter(|peak| peak.fft_pass_number >= first_pass)
+                        .map(|peak| FrequencyPeak {
+                            fft_pass_number: peak.fft_pass_number - first_pass,
+                            peak_magnitude: peak.peak_magnitude,
+                            corrected_peak_frequency_bin: peak.corrected_peak_frequency_bin,
+                        })
+                        .collect()
+                }),
+        }
+    }
+
+    /// Forget the peaks found before the last `number_samples` samples fed,
+    /// so that a long-lived generator keeps a sliding window of bounded
+    /// size. The positions of the remaining peaks are shifted accordingly.
+    pub fn keep_last(&mut self, number_samples: u32) {
+        if self.signature.number_samples <= number_samples {
+            return;
+        }
+
+        let dropped_passes = (self.signature.number_samples - number_samples) / 128;
+
+        self.signature.number_samples -= dropped_passes * 128;
+        self.num_spread_ffts_done -= dropped_passes;
+
+        for frequency_peaks in self.signature.frequency_band_to_sound_peaks.iter_mut() {
+            frequency_peaks.retain(|peak| peak.fft_pass_number >= dropped_passes);
+            for peak in frequency_peaks.iter_mut() {
+                peak.fft_pass_number -= dropped_passes;
+            }
+        }
+    }
+
+    /// Discard all the samples and peaks processed so far.
+    pub fn reset(&mut self) {
+        self.pending_samples.clear();
+
+        self.ring_buffer_of_samples.fill(0);
+        self.ring_buffer_of_samples_index = 0;
+
+        self.reordered_ring_buffer_of_samples.fill(0.0);
+        self.complex_fft_output.fill(Complex::zero());
+
+        self.fft_outputs.fill([0.0f32; 1025]);
+        self.fft_outputs_index = 0;
+
+        self.spread_fft_outputs.fill([0.0f32; 1025]);
+        self.spread_fft_outputs_index = 0;
+
+        self.num_spread_ffts_done = 0;
+
+        self.signature.number_samples = 0;
+        for frequency_peaks in self.signature.frequency_band_to_sound_peaks.iter_mut() {
+            frequency_peaks.clear();
+        }
+    }
+
+    /// Number of samples fed since the creation of the generator or the
+    /// last call to `reset()`.
+    pub fn number_samples(&self) -> u32 {
+        self.signature.number_samples
     }
 
     fn do_fft(&mut self, s16_mono_16khz_buffer: &[i16; 128]) {
@@ -165,7 +430,6 @@ impl SignatureGenerator {
         // Perform Fast Fourier transform
 
         self.fft_object
-            .plan_fft_forward(2048)
             .process(
                 &mut *self.reordered_ring_buffer_of_samples,
                 &mut *self.complex_fft_output,
@@ -320,3 +584,74 @@ impl SignatureGenerator {
         }
     }
 }
+
+impl Default for SignatureGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_sliding_window_snapshot() {
+    // The buffers of the generator need a larger stack than the one of
+    // test threads in debug builds
+
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(|| {
+            // A few seconds of tones changing every quarter of a second
+
+            let samples: Vec<f32> = (0..16000 * 20)
+                .map(|index| {
+                    let frequency = 300.0 + ((index / 4000) % 17) as f32 * 211.0;
+                    (index as f32 * frequency * std::f32::consts::TAU / 16000.0).sin() * 0.5
+                })
+                .collect();
+
+            let mut generator = SignatureGenerator::new();
+            for chunk in samples.chunks(1000) {
+                generator.feed(chunk);
+                if generator.number_samples() >= 16000 * 16 {
+                    generator.keep_last(16000 * 12);
+                }
+            }
+
+            let window = generator.snapshot_last(16000 * 12);
+            assert_eq!(window.number_samples, 16000 * 12);
+
+            // Once their FFT windows only cover the last 12 seconds, the peaks
+            // must be the same as the ones of a new generator fed these seconds
+
+            let fresh = SignatureGenerator::make_signature_from_buffer(&samples[16000 * 8..]);
+
+            for (window_peaks, fresh_peaks) in window
+                .frequency_band_to_sound_peaks
+                .iter()
+                .zip(fresh.frequency_band_to_sound_peaks.iter())
+            {
+                let settled = |peaks: &Vec<FrequencyPeak>| -> Vec<(u32, u16, u16)> {
+                    peaks
+                        .iter()
+                        .filter(|peak| peak.fft_pass_number >= 100)
+                        .map(|peak| {
+                            (
+                                peak.fft_pass_number,
+                                peak.peak_magnitude,
+                                peak.corrected_peak_frequency_bin,
+                            )
+                        })
+                        .collect()
+                };
+                assert_eq!(settled(window_peaks), settled(fresh_peaks));
+            }
+            assert!(
+                window
+                    .frequency_band_to_sound_peaks
+                    .iter()
+                    .any(|peaks| !peaks.is_empty())
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
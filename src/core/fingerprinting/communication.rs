@@ -1,11 +1,12 @@
-use gettextrs::gettext;
+use crate::utils::internationalization::gettext;
 use glib::source::Priority;
 use log::{debug, error, trace};
 use rand::prelude::IndexedRandom;
 use serde_json::{Value, json};
 use soup::prelude::SessionExt;
 use std::error::Error;
-use std::time::SystemTime;
+use std::fmt;
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
 use crate::core::fingerprinting::signature_format::DecodedSignature;
@@ -61,9 +62,191 @@ fn log_response(message: &soup::Message, response: &str) {
     }
 }
 
+/// An HTTP status returned by the recognition API which tells that the
+/// request should be retried later: 429 (Too Many Requests) or 5xx.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryableStatusError {
+    pub status_code: u32,
+    /// Delay requested by the server through the Retry-After header.
+    pub retry_after: Option<Duration>,
+}
+
+impl RetryableStatusError {
+    pub fn is_rate_limited(&self) -> bool {
+        self.status_code == 429
+    }
+}
+
+impl fmt::Display for RetryableStatusError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_rate_limited() {
+            write!(formatter, "{}", gettext("Your IP has been rate-limited"))
+        } else {
+            write!(
+                formatter,
+                "{}",
+                gettext("Shazam's servers could not answer (HTTP status %s)")
+                    .replace("%s", &self.status_code.to_string())
+            )
+        }
+    }
+}
+
+impl Error for RetryableStatusError {}
+
+/// Delays requested through Retry-After are shortened to this, so that an
+/// erroneous header does not stop recognizing songs for good.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+/// Parse the value of a Retry-After header, which is either a number of
+/// seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    let delay = if let Ok(seconds) = value.parse::<u64>() {
+        Duration::from_secs(seconds)
+    } else {
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        let delay = date.signed_duration_since(chrono::Utc::now());
+        delay.to_std().unwrap_or(Duration::ZERO)
+    };
+
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Base URL of Shazam's recognition API.
+pub const DEFAULT_RECOGNITION_ENDPOINT: &str = "https://amp.shazam.com";
+
+/// Environment variables setting the base URLs used for recognizing songs
+/// and for obtaining cover images, e.g. in order to use a stand-in server
+/// (see the `serve-fixtures` subcommand) rather than Shazam's.
+pub const RECOGNITION_ENDPOINT_VARIABLE: &str = "SONGREC_RECOGNITION_ENDPOINT";
+pub const COVER_ENDPOINT_VARIABLE: &str = "SONGREC_COVER_ENDPOINT";
+
+fn endpoint_from_environment(variable: &str) -> Option<String> {
+    std::env::var(variable)
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+}
+
+/// The base URL used for recognizing songs: the configured one if any, or
+/// else the one set in the environment, or else Shazam's.
+pub fn recognition_endpoint(configured_endpoint: Option<&str>) -> String {
+    configured_endpoint
+        .map(str::to_string)
+        .or_else(|| endpoint_from_environment(RECOGNITION_ENDPOINT_VARIABLE))
+        .unwrap_or_else(|| DEFAULT_RECOGNITION_ENDPOINT.to_string())
+}
+
+/// The base URL used for obtaining cover images: the configured one if
+/// any, or else the one set in the environment. Without one, cover images
+/// are obtained from the URLs returned by Shazam.
+pub fn cover_endpoint(configured_endpoint: Option<&str>) -> Option<String> {
+    configured_endpoint
+        .map(str::to_string)
+        .or_else(|| endpoint_from_environment(COVER_ENDPOINT_VARIABLE))
+}
+
+/// Replace the scheme, host and port of a cover image URL with the ones of
+/// the given base URL, keeping its path.
+fn rebase_url(url: &str, endpoint: &str) -> String {
+    let path = match url.find("://") {
+        Some(scheme_end) => match url[scheme_end + 3..].find('/') {
+            Some(path_start) => &url[scheme_end + 3 + path_start..],
+            None => "/",
+        },
+        None => url,
+    };
+
+    format!("{}{}", endpoint.trim_end_matches('/'), path)
+}
+
+/// Where recognition requests are made from, which sets the language of
+/// the returned metadata and helps Shazam return regionally relevant
+/// results.
+#[derive(Debug, Clone)]
+pub struct RequestLocale {
+    /// Language and country codes, e.g. "en" and "US".
+    pub language: String,
+    pub country: String,
+    /// IANA timezone name, e.g. "Europe/Paris".
+    pub timezone: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl RequestLocale {
+    /// The locale of the system, from the `LC_ALL`, `LC_MESSAGES` or `LANG`
+    /// environment variables and the system timezone. The geolocation is
+    /// not known from the system, and defaults to the one SongRec always
+    /// used before it could be set.
+    pub fn from_system() -> Self {
+        Self::from_environment(|variable| std::env::var(variable).ok())
+    }
+
+    fn from_environment(get_variable: impl Fn(&str) -> Option<String>) -> Self {
+        let mut request_locale = RequestLocale {
+            language: "en".to_string(),
+            country: "US".to_string(),
+            timezone: iana_time_zone::get_timezone().unwrap_or_else(|_| "Europe/Paris".to_string()),
+            latitude: 45.0,
+            longitude: 2.0,
+        };
+
+        let system_locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|variable| get_variable(variable))
+            .find(|value| !value.is_empty());
+
+        if let Some(system_locale) = system_locale {
+            request_locale.set_locale(&system_locale);
+        }
+
+        request_locale
+    }
+
+    /// Set the language and country from a locale name such as "fr_FR",
+    /// "fr-FR" or "fr_FR.UTF-8". Names without a country, such as "C" or
+    /// "POSIX", are ignored.
+    pub fn set_locale(&mut self, locale: &str) {
+        let locale = locale.split(['.', '@']).next().unwrap_or_default();
+
+        if let Some((language, country)) = locale.split_once(['_', '-'])
+            && !language.is_empty()
+            && !country.is_empty()
+        {
+            self.language = language.to_lowercase();
+            self.country = country.to_uppercase();
+        }
+    }
+}
+
+impl Default for RequestLocale {
+    fn default() -> Self {
+        Self::from_system()
+    }
+}
+
 pub async fn recognize_song_from_signature(
     session: &soup::Session,
     signature: &DecodedSignature,
+) -> Result<Value, Box<dyn Error>> {
+    recognize_song_from_signature_with_endpoint(
+        session,
+        &recognition_endpoint(None),
+        &RequestLocale::from_system(),
+        signature,
+    )
+    .await
+}
+
+/// Same as `recognize_song_from_signature`, against the recognition API
+/// found at the given base URL and from the given locale.
+pub async fn recognize_song_from_signature_with_endpoint(
+    session: &soup::Session,
+    endpoint: &str,
+    request_locale: &RequestLocale,
+    signature: &DecodedSignature,
 ) -> Result<Value, Box<dyn Error>> {
     session.set_user_agent(USER_AGENTS.choose(&mut rand::rng()).unwrap());
 
@@ -74,8 +257,8 @@ pub async fn recognize_song_from_signature(
     let post_data = json!({
         "geolocation": {
             "altitude": 300,
-            "latitude": 45,
-            "longitude": 2
+            "latitude": request_locale.latitude,
+            "longitude": request_locale.longitude
         },
         "signature": {
             "samplems": (signature.number_samples as f32 / signature.sample_rate_hz as f32 * 1000.) as u32,
@@ -83,14 +266,14 @@ pub async fn recognize_song_from_signature(
             "uri": signature.encode_to_uri()?
         },
         "timestamp": timestamp_ms as u32,
-        "timezone": "Europe/Paris"
+        "timezone": request_locale.timezone
     }).to_string();
 
     let uuid_1 = Uuid::new_v4().hyphenated().to_string().to_uppercase();
     let uuid_2 = Uuid::new_v4().hyphenated().to_string();
 
     let url = format!(
-        "https://amp.shazam.com/discovery/v5/en/US/android/-/tag/{}/{}\
+        "{}/discovery/v5/{}/{}/android/-/tag/{}/{}\
 ?sync=true\
 &webv3=true\
 &sampling=true\
@@ -98,14 +281,21 @@ pub async fn recognize_song_from_signature(
 &shazamapiversion=v3\
 &sharehub=true\
 &video=v3",
-        uuid_1, uuid_2
+        endpoint.trim_end_matches('/'),
+        request_locale.language,
+        request_locale.country,
+        uuid_1,
+        uuid_2
     );
 
     let message = soup::Message::from_encoded_form("POST", &url, post_data.clone().into())?;
     message.set_force_http1(true);
 
     let headers = message.request_headers().unwrap();
-    headers.append("Content-Language", "en_US");
+    headers.append(
+        "Content-Language",
+        &format!("{}_{}", request_locale.language, request_locale.country),
+    );
     headers.set_content_type(Some("application/json"), None);
 
     log_request(&message, &post_data);
@@ -118,11 +308,16 @@ pub async fn recognize_song_from_signature(
 
     log_response(&message, &decoded_resp);
 
-    if message.status_code() == 429 {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::QuotaExceeded,
-            gettext("Your IP has been rate-limited").as_str(),
-        )));
+    let status_code = message.status_code();
+
+    if status_code == 429 || (500..600).contains(&status_code) {
+        return Err(Box::new(RetryableStatusError {
+            status_code,
+            retry_after: message
+                .response_headers()
+                .and_then(|headers| headers.one("Retry-After"))
+                .and_then(|value| parse_retry_after(&value)),
+        }));
     }
 
     Ok(serde_json::from_slice(&response[..])?)
@@ -131,14 +326,29 @@ pub async fn recognize_song_from_signature(
 pub async fn obtain_raw_cover_image(
     session: &soup::Session,
     url: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    obtain_raw_cover_image_with_endpoint(session, cover_endpoint(None).as_deref(), url).await
+}
+
+/// Same as `obtain_raw_cover_image`, from the server found at the given
+/// base URL rather than the one of the cover image URL, if any.
+pub async fn obtain_raw_cover_image_with_endpoint(
+    session: &soup::Session,
+    endpoint: Option<&str>,
+    url: &str,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
     session.set_user_agent(USER_AGENTS.choose(&mut rand::rng()).unwrap());
 
-    let message = soup::Message::new("GET", url)?;
+    let url = match endpoint {
+        Some(endpoint) => rebase_url(url, endpoint),
+        None => url.to_string(),
+    };
+
+    let message = soup::Message::new("GET", &url)?;
     message.set_force_http1(true);
 
-    let headers = message.request_headers().unwrap();
-    headers.append("Content-Language", "en_US");
+    // Cover images do not depend on the locale of the request, unlike the
+    // metadata of songs
 
     log_request(&message, "");
 
@@ -146,8 +356,80 @@ pub async fn obtain_raw_cover_image(
         .send_and_read_future(&message, Priority::DEFAULT)
         .await?;
 
-    let resp_header = format!("{:?}...", &response[..32]);
+    let resp_header = format!("{:?}...", &response[..response.len().min(32)]);
     log_response(&message, &resp_header);
 
+    if message.status_code() != 200 {
+        return Err(Box::new(std::io::Error::other(
+            gettext("Could not obtain the cover image (HTTP status %s)")
+                .replace("%s", &message.status_code().to_string()),
+        )));
+    }
+
     Ok(response[..].to_vec())
 }
+
+#[test]
+fn test_set_locale() {
+    let mut request_locale = RequestLocale::from_environment(|_| None);
+
+    request_locale.set_locale("fr_FR.UTF-8");
+    assert_eq!(request_locale.language, "fr");
+    assert_eq!(request_locale.country, "FR");
+
+    request_locale.set_locale("C");
+    assert_eq!(request_locale.language, "fr");
+    assert_eq!(request_locale.country, "FR");
+
+    request_locale.set_locale("pt-BR@euro");
+    assert_eq!(request_locale.language, "pt");
+    assert_eq!(request_locale.country, "BR");
+}
+
+#[test]
+fn test_locale_from_environment() {
+    let request_locale = RequestLocale::from_environment(|_| None);
+    assert_eq!(request_locale.language, "en");
+    assert_eq!(request_locale.country, "US");
+
+    let request_locale = RequestLocale::from_environment(|variable| {
+        (variable == "LANG").then(|| "de_DE.UTF-8".to_string())
+    });
+    assert_eq!(request_locale.language, "de");
+    assert_eq!(request_locale.country, "DE");
+
+    let request_locale = RequestLocale::from_environment(|variable| match variable {
+        "LC_ALL" => Some("C".to_string()),
+        "LANG" => Some("de_DE.UTF-8".to_string()),
+        _ => None,
+    });
+    assert_eq!(request_locale.language, "en");
+    assert_eq!(request_locale.country, "US");
+
+    let request_locale = RequestLocale::from_environment(|variable| match variable {
+        "LC_ALL" => Some(String::new()),
+        "LC_MESSAGES" => Some("es_MX".to_string()),
+        "LANG" => Some("de_DE.UTF-8".to_string()),
+        _ => None,
+    });
+    assert_eq!(request_locale.language, "es");
+    assert_eq!(request_locale.country, "MX");
+}
+
+#[test]
+fn test_parse_retry_after() {
+    assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+    assert_eq!(
+        parse_retry_after(&u64::MAX.to_string()),
+        Some(MAX_RETRY_AFTER)
+    );
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(
+        parse_retry_after("Fri, 31 Dec 9999 23:59:59 GMT"),
+        Some(MAX_RETRY_AFTER)
+    );
+    assert_eq!(parse_retry_after("soon"), None);
+}
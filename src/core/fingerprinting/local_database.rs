@@ -0,0 +1,415 @@
+//! This module contains an offline index of audio fingerprints, allowing to
+//! recognize songs from a local catalogue (e.g. unreleased tracks or jingles)
+//! without sending anything to Shazam's servers.
+//!
+//! Songs are indexed as hashes of pairs of nearby frequency peaks within the
+//! same frequency band, each hash pointing to the time at which the first
+//! peak of the pair occurs. An incoming signature is then scored against the
+//! index by counting, for each indexed song, how many of its hashes agree on
+//! a common time offset.
+
+use crate::utils::internationalization::gettext;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+
+const DATABASE_MAGIC: u32 = 0x42445253; // "SRDB"
+const DATABASE_VERSION: u32 = 1;
+
+/// Number of following peaks of the same band that each peak is paired with.
+const TARGET_ZONE_SIZE: usize = 5;
+
+/// Maximal time distance between two paired peaks, in FFT passes (there are
+/// 125 FFT passes per second at 16 KHz, so this is about 2 seconds).
+const TARGET_ZONE_MAX_DELTA: u32 = 255;
+
+/// Minimal number of hashes agreeing on a time offset for a song to be
+/// considered as matching.
+const MIN_MATCH_SCORE: usize = 8;
+
+/// Maximal length of the names and paths stored in a database file, in
+/// bytes, above which the file is considered as corrupt.
+const MAX_STRING_LENGTH: u32 = 64 * 1024;
+
+pub struct LocalTrack {
+    pub name: String,
+    pub path: String,
+    pub number_samples: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LocalMatch {
+    pub track_name: String,
+    pub track_path: String,
+    /// Number of peak pair hashes agreeing on the matched time offset.
+    pub score: usize,
+    /// Position of the start of the query signature within the indexed song.
+    pub offset_secs: f32,
+}
+
+#[derive(Default)]
+pub struct LocalDatabase {
+    tracks: Vec<LocalTrack>,
+    /// Maps a peak pair hash to a list of (track index, FFT pass number)
+    /// tuples.
+    hashes: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+impl LocalDatabase {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Load a database file, or return an empty database if the file does
+    /// not exist yet.
+    pub fn load_or_create(database_path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !database_path.exists() {
+            return Ok(LocalDatabase::new());
+        }
+        LocalDatabase::load(database_path)
+    }
+
+    pub fn load(database_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let invalid_database = || {
+            Box::new(std::io::Error::other(gettext(
+                "Invalid or unsupported local database file",
+            )))
+        };
+
+        let mut reader = BufReader::new(std::fs::File::open(database_path)?);
+
+        if reader.read_u32::<LittleEndian>()? != DATABASE_MAGIC
+            || reader.read_u32::<LittleEndian>()? != DATABASE_VERSION
+        {
+            return Err(invalid_database());
+        }
+
+        let mut database = LocalDatabase::new();
+
+        let number_tracks = reader.read_u32::<LittleEndian>()?;
+
+        for _ in 0..number_tracks {
+            database.tracks.push(LocalTrack {
+                name: read_string(&mut reader)?,
+                path: read_string(&mut reader)?,
+                number_samples: reader.read_u32::<LittleEndian>()?,
+            });
+        }
+
+        let number_hashes = reader.read_u32::<LittleEndian>()?;
+
+        for _ in 0..number_hashes {
+            let hash = reader.read_u32::<LittleEndian>()?;
+            let track_index = reader.read_u32::<LittleEndian>()?;
+            let fft_pass_number = reader.read_u32::<LittleEndian>()?;
+
+            if track_index >= number_tracks {
+                return Err(invalid_database());
+            }
+
+            database
+                .hashes
+                .entry(hash)
+                .or_default()
+                .push((track_index, fft_pass_number));
+        }
+
+        debug!(
+            "Loaded local database from {}: {} tracks, {} hashes",
+            database_path.display(),
+            number_tracks,
+            number_hashes
+        );
+
+        Ok(database)
+    }
+
+    pub fn save(&self, database_path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(std::fs::File::create(database_path)?);
+
+        writer.write_u32::<LittleEndian>(DATABASE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(DATABASE_VERSION)?;
+
+        writer.write_u32::<LittleEndian>(self.tracks.len() as u32)?;
+
+        for track in &self.tracks {
+            write_string(&mut writer, &track.name)?;
+            write_string(&mut writer, &track.path)?;
+            writer.write_u32::<LittleEndian>(track.number_samples)?;
+        }
+
+        let number_hashes: usize = self.hashes.values().map(|entries| entries.len()).sum();
+        writer.write_u32::<LittleEndian>(number_hashes as u32)?;
+
+        for (hash, entries) in &self.hashes {
+            for (track_index, fft_pass_number) in entries {
+                writer.write_u32::<LittleEndian>(*hash)?;
+                writer.write_u32::<LittleEndian>(*track_index)?;
+                writer.write_u32::<LittleEndian>(*fft_pass_number)?;
+            }
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.tracks.iter().any(|track| track.path == path)
+    }
+
+    pub fn add_track(&mut self, name: &str, path: &str, signature: &DecodedSignature) {
+        let track_index = self.tracks.len() as u32;
+
+        self.tracks.push(LocalTrack {
+            name: name.to_string(),
+            path: path.to_string(),
+            number_samples: signature.number_samples,
+        });
+
+        for (hash, fft_pass_number) in hash_peak_pairs(signature) {
+            self.hashes
+                .entry(hash)
+                .or_default()
+                .push((track_index, fft_pass_number));
+        }
+    }
+
+    /// Fingerprint the whole contents of an audio file and add it to the
+    /// database. Returns false if the file was already indexed.
+    pub fn add_file(&mut self, file_path: &Path) -> Result<bool, Box<dyn Error>> {
+        let path_string = file_path.to_string_lossy().into_owned();
+
+        if self.contains_path(&path_string) {
+            return Ok(false);
+        }
+
+        let signature = SignatureGenerator::make_full_signature_from_file(&path_string)?;
+
+        let name = file_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path_string.clone());
+
+        self.add_track(&name, &path_string, &signature);
+
+        Ok(true)
+    }
+
+    /// Score a signature against the indexed songs, returning the matching
+    /// songs sorted from the best to the worst match.
+    pub fn find_matches(&self, signature: &DecodedSignature) -> Vec<LocalMatch> {
+        // For each song, count the hashes agreeing on a given time offset
+        // between the indexed song and the query
+
+        let mut offset_votes: HashMap<(u32, i64), usize> = HashMap::new();
+
+        for (hash, query_fft_pass_number) in hash_peak_pairs(signature) {
+            if let Some(entries) = self.hashes.get(&hash) {
+                for (track_index, fft_pass_number) in entries {
+                    *offset_votes
+                        .entry((
+                            *track_index,
+                            *fft_pass_number as i64 - query_fft_pass_number as i64,
+                        ))
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        // Keep the best time offset for each song
+
+        let mut best_offsets: HashMap<u32, (i64, usize)> = HashMap::new();
+
+        for ((track_index, offset), score) in offset_votes {
+            let best_offset = best_offsets.entry(track_index).or_insert((offset, 0));
+            if score > best_offset.1 {
+                *best_offset = (offset, score);
+            }
+        }
+
+        let mut matches: Vec<LocalMatch> = best_offsets
+            .into_iter()
+            .filter(|(_track_index, (_offset, score))| *score >= MIN_MATCH_SCORE)
+            .map(|(track_index, (offset, score))| {
+                let track = &self.tracks[track_index as usize];
+                LocalMatch {
+                    track_name: track.name.clone(),
+                    track_path: track.path.clone(),
+                    score,
+                    offset_secs: offset as f32 * 128.0 / signature.sample_rate_hz as f32,
+                }
+            })
+            .collect();
+
+        matches.sort_by_key(|local_match| std::cmp::Reverse(local_match.score));
+
+        matches
+    }
+}
+
+/// Generate (hash, FFT pass number of the anchor peak) tuples from the peaks
+/// of a signature. A hash packs the quantized frequency of two peaks of the
+/// same band along with their time distance.
+fn hash_peak_pairs(signature: &DecodedSignature) -> Vec<(u32, u32)> {
+    let mut hashes: Vec<(u32, u32)> = vec![];
+
+    for frequency_peaks in &signature.frequency_band_to_sound_peaks {
+        for (index, anchor_peak) in frequency_peaks.iter().enumerate() {
+            let anchor_frequency = (anchor_peak.corrected_peak_frequency_bin >> 6) as u32;
+
+            for target_peak in frequency_peaks
+                .iter()
+                .skip(index + 1)
+                .filter(|peak| peak.fft_pass_number > anchor_peak.fft_pass_number)
+                .take(TARGET_ZONE_SIZE)
+            {
+                let time_delta = target_peak.fft_pass_number - anchor_peak.fft_pass_number;
+
+                if time_delta > TARGET_ZONE_MAX_DELTA {
+                    break;
+                }
+
+                let target_frequency = (target_peak.corrected_peak_frequency_bin >> 6) as u32;
+
+                hashes.push((
+                    ((anchor_frequency & 0x3ff) << 18)
+                        | ((target_frequency & 0x3ff) << 8)
+                        | time_delta,
+                    anchor_peak.fft_pass_number,
+                ));
+            }
+        }
+    }
+
+    hashes
+}
+
+/// List the files contained in a directory and its subdirectories, or
+/// return the path itself if it is a file.
+pub fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        match std::fs::read_dir(path) {
+            Ok(entries) => {
+                let mut entries: Vec<PathBuf> =
+                    entries.flatten().map(|entry| entry.path()).collect();
+                entries.sort();
+                for entry in entries {
+                    collect_files(&entry, files);
+                }
+            }
+            Err(error) => {
+                warn!("Could not list {}: {}", path.display(), error);
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, Box<dyn Error>> {
+    let length = reader.read_u32::<LittleEndian>()?;
+    if length > MAX_STRING_LENGTH {
+        return Err(Box::new(std::io::Error::other(gettext(
+            "Invalid or unsupported local database file",
+        ))));
+    }
+    let mut buffer = vec![0u8; length as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+fn write_string(writer: &mut impl Write, string: &str) -> Result<(), Box<dyn Error>> {
+    writer.write_u32::<LittleEndian>(string.len() as u32)?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn test_local_database_match() {
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+
+    let mut reference = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 16000 * 60,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    let mut seed: u32 = 1;
+    for fft_pass_number in (0..7500).step_by(7) {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        reference.frequency_band_to_sound_peaks[2].push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 10000,
+            corrected_peak_frequency_bin: (200 + (seed >> 16) % 400) as u16 * 64,
+        });
+    }
+
+    // Take 12 seconds starting at 20 seconds as the query
+
+    let mut query = reference.clone();
+    query.number_samples = 16000 * 12;
+    query.frequency_band_to_sound_peaks[2] = reference.frequency_band_to_sound_peaks[2]
+        .iter()
+        .filter(|peak| peak.fft_pass_number >= 2500 && peak.fft_pass_number < 4000)
+        .map(|peak| FrequencyPeak {
+            fft_pass_number: peak.fft_pass_number - 2500,
+            ..peak.clone()
+        })
+        .collect();
+
+    let mut database = LocalDatabase::new();
+    database.add_track("Jingle", "jingle.wav", &reference);
+
+    let matches = database.find_matches(&query);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].track_name, "Jingle");
+    assert_eq!(matches[0].offset_secs, 20.0);
+}
+
+#[test]
+fn test_local_database_corrupt_file() {
+    let mut database = LocalDatabase::new();
+    database.add_track(
+        "Jingle",
+        "jingle.wav",
+        &DecodedSignature {
+            sample_rate_hz: 16000,
+            number_samples: 16000,
+            frequency_band_to_sound_peaks: Default::default(),
+        },
+    );
+    database.hashes.insert(1234, vec![(0, 10)]);
+
+    let database_file = tempfile::NamedTempFile::new().unwrap();
+    database.save(database_file.path()).unwrap();
+    let valid_bytes = std::fs::read(database_file.path()).unwrap();
+    assert!(LocalDatabase::load(database_file.path()).is_ok());
+
+    // A hash pointing to a track that does not exist
+
+    let mut bytes = valid_bytes.clone();
+    let track_index_position = bytes.len() - 8;
+    bytes[track_index_position..track_index_position + 4].copy_from_slice(&7u32.to_le_bytes());
+    std::fs::write(database_file.path(), &bytes).unwrap();
+    assert!(LocalDatabase::load(database_file.path()).is_err());
+
+    // A name of 4 GB
+
+    let mut bytes = valid_bytes.clone();
+    bytes[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(database_file.path(), &bytes).unwrap();
+    assert!(LocalDatabase::load(database_file.path()).is_err());
+
+    // A truncated file
+
+    std::fs::write(database_file.path(), &valid_bytes[..valid_bytes.len() - 3]).unwrap();
+    assert!(LocalDatabase::load(database_file.path()).is_err());
+}
@@ -0,0 +1,384 @@
+//! This module contains code used to choose which excerpts of an audio file
+//! are recognized, as only 12 seconds of audio are sent to Shazam at once.
+
+use crate::utils::internationalization::gettext;
+use rodio::nz;
+use rodio::source::UniformSourceIterator;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+
+/// Longest excerpt that may be sent to Shazam, in seconds.
+pub const MAX_WINDOW_SECS: u64 = 12;
+
+/// Positions of the excerpts tried by the "multi" strategy, as fractions of
+/// the file, in order.
+const MULTI_WINDOW_POSITIONS: [f64; 5] = [0.5, 0.25, 0.75, 0.0, 1.0];
+
+/// Maximum number of excerpts whose signature is kept while decoding a file
+/// whose duration is unknown, among which the requested ones are chosen.
+const MAX_STREAMED_CANDIDATES: usize = 64;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WindowStrategy {
+    /// Take the excerpt from the middle of the file.
+    #[default]
+    Middle,
+    Start,
+    End,
+    /// Try excerpts from several positions of the file in turn, until
+    /// one of them is recognized.
+    Multi,
+}
+
+impl FromStr for WindowStrategy {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "middle" => Ok(WindowStrategy::Middle),
+            "start" => Ok(WindowStrategy::Start),
+            "end" => Ok(WindowStrategy::End),
+            "multi" => Ok(WindowStrategy::Multi),
+            _ => Err(Box::new(std::io::Error::other(gettext(
+                "Unknown window strategy, expected middle, start, end or multi",
+            )))),
+        }
+    }
+}
+
+/// Describes the excerpts of an audio file to generate signatures for.
+#[derive(Clone, Copy, Debug)]
+pub struct RecognitionWindow {
+    /// Position of the excerpt within the file. When set, the strategy is
+    /// ignored.
+    pub offset: Option<Duration>,
+    pub duration: Duration,
+    pub strategy: WindowStrategy,
+}
+
+impl Default for RecognitionWindow {
+    fn default() -> Self {
+        RecognitionWindow {
+            offset: None,
+            duration: Duration::from_secs(MAX_WINDOW_SECS),
+            strategy: WindowStrategy::Middle,
+        }
+    }
+}
+
+impl RecognitionWindow {
+    /// Whether the duration of the file is needed for computing the
+    /// positions of the excerpts.
+    fn needs_file_duration(&self) -> bool {
+        self.offset.is_none() && self.strategy != WindowStrategy::Start
+    }
+
+    /// Positions of the excerpts to try in turn, for a file lasting
+    /// `file_duration`.
+    pub fn starts(&self, file_duration: Duration) -> Vec<Duration> {
+        if let Some(offset) = self.offset {
+            return vec![offset];
+        }
+
+        let latest_start = file_duration.saturating_sub(self.duration);
+
+        match self.strategy {
+            WindowStrategy::Middle => vec![latest_start / 2],
+            WindowStrategy::Start => vec![Duration::ZERO],
+            WindowStrategy::End => vec![latest_start],
+            WindowStrategy::Multi => {
+                let mut starts: Vec<Duration> = vec![];
+
+                // Short files may not leave room for distinct excerpts
+
+                for position in MULTI_WINDOW_POSITIONS {
+                    let start = latest_start.mul_f64(position);
+
+                    if !starts.contains(&start) {
+                        starts.push(start);
+                    }
+                }
+
+                starts
+            }
+        }
+    }
+}
+
+impl SignatureGenerator {
+    /// Generate a signature for each excerpt of an audio file described by
+    /// `window`, in the order in which they should be tried. Only the
+    /// excerpts are decoded, when the format allows seeking.
+    pub fn make_signatures_from_file(
+        file_path: &str,
+        window: &RecognitionWindow,
+    ) -> Result<Vec<DecodedSignature>, Box<dyn Error>> {
+        let stdin_file = SignatureGenerator::copy_stdin_if_needed(file_path)?;
+        let file_path = stdin_file
+            .as_ref()
+            .map_or(Path::new(file_path), |stdin_file| stdin_file.path());
+
+        let number_samples = (window.duration.as_secs_f64() * 16000.0) as usize;
+
+        let file_duration = if window.needs_file_duration() {
+            match SignatureGenerator::file_duration(file_path)? {
+                Some(file_duration) => file_duration,
+                None => {
+                    return SignatureGenerator::make_signatures_from_stream(
+                        file_path,
+                        window,
+                        number_samples,
+                    );
+                }
+            }
+        } else {
+            Duration::ZERO
+        };
+
+        let mut signatures: Vec<DecodedSignature> = vec![];
+
+        for start in window.starts(file_duration) {
+            let mut raw_pcm_samples =
+                SignatureGenerator::decode_file_window(file_path, start, number_samples)?;
+
+            if raw_pcm_samples.is_empty() && !start.is_zero() {
+                return Err(Box::new(std::io::Error::other(gettext(
+                    "The requested offset is beyond the end of the file",
+                ))));
+            }
+
+            // Pad the input to the whole window in order to avoid missing
+            // data at the end of the input
+
+            raw_pcm_samples.resize(number_samples, 0.0);
+
+            signatures.push(SignatureGenerator::make_signature_from_buffer(
+                &raw_pcm_samples,
+            ));
+        }
+
+        Ok(signatures)
+    }
+
+    /// Generate the signatures described by `window` in a single decoding
+    /// pass, for the formats that don't provide the duration of the file
+    /// in their headers. The signature of an excerpt is kept every `step`
+    /// samples, and the step doubles whenever too many of them were kept,
+    /// so that memory stays bounded whatever the length of the file. Each
+    /// requested excerpt is then approximated by the nearest one kept, and
+    /// the last excerpt of the file is always kept.
+    fn make_signatures_from_stream(
+        file_path: &Path,
+        window: &RecognitionWindow,
+        number_samples: usize,
+    ) -> Result<Vec<DecodedSignature>, Box<dyn Error>> {
+        let mut raw_pcm_samples = UniformSourceIterator::new(
+            SignatureGenerator::open_decoder(file_path, Duration::ZERO, None)?,
+            nz!(1),
+            nz!(16000),
+        );
+
+        let mut generator = SignatureGenerator::new();
+        let mut chunk: Vec<f32> = Vec::with_capacity(16000);
+        let mut position: usize = 0;
+
+        let mut candidates: Vec<(usize, DecodedSignature)> = vec![];
+        let mut step: usize = 16000;
+        let mut next_start: usize = 0;
+
+        'decoding: loop {
+            // Feed the samples up to the end of the next excerpt, dropping
+            // the peaks that can't be part of it anymore
+
+            let next_end = next_start + number_samples;
+
+            while position < next_end {
+                chunk.clear();
+                chunk.extend(
+                    raw_pcm_samples
+                        .by_ref()
+                        .take((next_end - position).min(16000)),
+                );
+
+                if chunk.is_empty() {
+                    break 'decoding;
+                }
+
+                generator.feed(&chunk);
+                generator.keep_last(number_samples as u32);
+                position += chunk.len();
+            }
+
+            candidates.push((next_start, generator.snapshot_last(number_samples as u32)));
+
+            if candidates.len() > MAX_STREAMED_CANDIDATES {
+                step *= 2;
+                candidates.retain(|(start, _)| start % step == 0);
+            }
+
+            next_start = candidates.last().unwrap().0 + step;
+        }
+
+        let file_duration = Duration::from_secs_f64(position as f64 / 16000.0);
+
+        // Pad the input to the whole window in order to avoid missing
+        // data at the end of the input
+
+        if position < number_samples {
+            generator.feed(&vec![0.0; number_samples - position]);
+        }
+
+        let last_start = position.saturating_sub(number_samples);
+
+        if candidates
+            .last()
+            .is_none_or(|(start, _)| *start != last_start)
+        {
+            candidates.push((last_start, generator.snapshot_last(number_samples as u32)));
+        }
+
+        let mut chosen_candidates: Vec<usize> = vec![];
+
+        for start in window.starts(file_duration) {
+            let start = (start.as_secs_f64() * 16000.0) as usize;
+
+            let nearest_candidate = (0..candidates.len())
+                .min_by_key(|&index| candidates[index].0.abs_diff(start))
+                .unwrap();
+
+            if !chosen_candidates.contains(&nearest_candidate) {
+                chosen_candidates.push(nearest_candidate);
+            }
+        }
+
+        Ok(chosen_candidates
+            .into_iter()
+            .map(|index| candidates[index].1.clone())
+            .collect())
+    }
+}
+
+/// Write 16 KHz mono samples to a temporary .WAV file.
+#[cfg(test)]
+fn write_test_wav(samples: &[f32]) -> tempfile::NamedTempFile {
+    use std::io::Write;
+
+    let data_size = (samples.len() * 2) as u32;
+
+    let mut header: Vec<u8> = vec![];
+    header.extend(b"RIFF");
+    header.extend((36 + data_size).to_le_bytes());
+    header.extend(b"WAVEfmt ");
+    header.extend(16u32.to_le_bytes());
+    header.extend(1u16.to_le_bytes()); // PCM
+    header.extend(1u16.to_le_bytes()); // Mono
+    header.extend(16000u32.to_le_bytes());
+    header.extend(32000u32.to_le_bytes());
+    header.extend(2u16.to_le_bytes());
+    header.extend(16u16.to_le_bytes());
+    header.extend(b"data");
+    header.extend(data_size.to_le_bytes());
+
+    let mut wav_file = tempfile::NamedTempFile::new().unwrap();
+    wav_file.write_all(&header).unwrap();
+    for sample in samples {
+        wav_file
+            .write_all(&((sample * 32767.0) as i16).to_le_bytes())
+            .unwrap();
+    }
+    wav_file.flush().unwrap();
+    wav_file
+}
+
+#[test]
+fn test_streamed_signatures() {
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+
+    // The buffers of the generator need a larger stack than the one of
+    // test threads in debug builds
+
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(|| {
+            // 40 seconds of tones changing every quarter of a second
+
+            let samples: Vec<f32> = (0..16000 * 40)
+                .map(|index| {
+                    let frequency = 300.0 + ((index / 4000) % 17) as f32 * 211.0;
+                    (index as f32 * frequency * std::f32::consts::TAU / 16000.0).sin() * 0.5
+                })
+                .collect();
+            let wav_file = write_test_wav(&samples);
+
+            let window = RecognitionWindow {
+                strategy: WindowStrategy::Multi,
+                ..Default::default()
+            };
+            let number_samples = 16000 * 12;
+
+            let streamed = SignatureGenerator::make_signatures_from_stream(
+                wav_file.path(),
+                &window,
+                number_samples,
+            )
+            .unwrap();
+
+            // The excerpts of this file start at whole seconds, so that the
+            // streamed ones are exactly the requested ones, and their peaks
+            // must be the same as when seeking to them, once their FFT
+            // windows only cover the excerpt
+
+            let starts = window.starts(Duration::from_secs(40));
+            assert_eq!(streamed.len(), starts.len());
+
+            for (signature, start) in streamed.iter().zip(starts) {
+                assert_eq!(signature.number_samples, number_samples as u32);
+
+                let raw_pcm_samples =
+                    SignatureGenerator::decode_file_window(wav_file.path(), start, number_samples)
+                        .unwrap();
+                let seeked = SignatureGenerator::make_signature_from_buffer(&raw_pcm_samples);
+
+                for (streamed_peaks, seeked_peaks) in signature
+                    .frequency_band_to_sound_peaks
+                    .iter()
+                    .zip(seeked.frequency_band_to_sound_peaks.iter())
+                {
+                    let settled = |peaks: &Vec<FrequencyPeak>| -> Vec<(u32, u16, u16)> {
+                        peaks
+                            .iter()
+                            .filter(|peak| peak.fft_pass_number >= 100)
+                            .map(|peak| {
+                                (
+                                    peak.fft_pass_number,
+                                    peak.peak_magnitude,
+                                    peak.corrected_peak_frequency_bin,
+                                )
+                            })
+                            .collect()
+                    };
+                    assert_eq!(settled(streamed_peaks), settled(seeked_peaks));
+                }
+            }
+
+            // Files shorter than the window are padded
+
+            let short_file = write_test_wav(&samples[..16000 * 5]);
+            let streamed = SignatureGenerator::make_signatures_from_stream(
+                short_file.path(),
+                &window,
+                number_samples,
+            )
+            .unwrap();
+            assert_eq!(streamed.len(), 1);
+            assert_eq!(streamed[0].number_samples, number_samples as u32);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
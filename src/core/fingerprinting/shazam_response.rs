@@ -0,0 +1,307 @@
+//! This module contains the structure of the responses of Shazam's
+//! recognition API, as far as SongRec knows it. Every field is optional and
+//! unknown fields are ignored, so that only a change in the type of a known
+//! field makes the deserialization fail.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamResponse {
+    /// The matching excerpts of tracks, the best one first. This is empty
+    /// when nothing matched.
+    pub matches: Vec<ShazamMatch>,
+    pub track: Option<ShazamTrack>,
+    pub tagid: Option<String>,
+    pub timestamp: Option<u64>,
+    pub timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamMatch {
+    pub id: Option<String>,
+    /// Position of the start of the recording within the track, in seconds.
+    pub offset: Option<f64>,
+    /// Relative differences between the speed and pitch of the recording
+    /// and the ones of the track.
+    #[serde(rename = "timeskew")]
+    pub time_skew: Option<f64>,
+    #[serde(rename = "frequencyskew")]
+    pub frequency_skew: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamTrack {
+    pub key: Option<String>,
+    pub title: Option<String>,
+    /// The name of the artist.
+    pub subtitle: Option<String>,
+    pub isrc: Option<String>,
+    pub url: Option<String>,
+    pub images: Option<TrackImages>,
+    pub genres: Option<TrackGenres>,
+    pub sections: Vec<TrackSection>,
+    pub hub: Option<TrackHub>,
+    pub share: Option<TrackShare>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TrackImages {
+    pub background: Option<String>,
+    pub coverart: Option<String>,
+    pub coverarthq: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TrackGenres {
+    pub primary: Option<String>,
+}
+
+/// A tab of the track page, such as "SONG", "LYRICS", "VIDEO" or "ARTIST".
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TrackSection {
+    #[serde(rename = "type")]
+    pub section_type: Option<String>,
+    pub tabname: Option<String>,
+    /// Labelled metadata of the song, such as its album and release year.
+    pub metadata: Vec<SectionMetadatum>,
+    /// Lines of the lyrics.
+    pub text: Vec<String>,
+    pub footer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SectionMetadatum {
+    /// The label of the metadatum, translated in the requested language.
+    pub title: Option<String>,
+    pub text: Option<String>,
+}
+
+/// Links to the track on music stores and streaming services.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TrackHub {
+    #[serde(rename = "type")]
+    pub hub_type: Option<String>,
+    pub displayname: Option<String>,
+    pub explicit: Option<bool>,
+    pub actions: Vec<HubAction>,
+    pub options: Vec<HubOption>,
+    pub providers: Vec<HubProvider>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HubAction {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub action_type: Option<String>,
+    pub id: Option<String>,
+    pub uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HubOption {
+    pub caption: Option<String>,
+    pub providername: Option<String>,
+    #[serde(rename = "type")]
+    pub option_type: Option<String>,
+    pub actions: Vec<HubAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct HubProvider {
+    pub caption: Option<String>,
+    #[serde(rename = "type")]
+    pub provider_type: Option<String>,
+    pub actions: Vec<HubAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TrackShare {
+    pub subject: Option<String>,
+    pub text: Option<String>,
+    pub href: Option<String>,
+    pub image: Option<String>,
+}
+
+impl ShazamTrack {
+    /// The section holding the metadata of the song, if any.
+    pub fn song_section(&self) -> Option<&TrackSection> {
+        self.sections
+            .iter()
+            .find(|section| section.section_type.as_deref() == Some("SONG"))
+    }
+
+    /// The lines of the lyrics of the song, if Shazam knows them.
+    pub fn lyrics(&self) -> Option<&[String]> {
+        self.sections
+            .iter()
+            .find(|section| section.section_type.as_deref() == Some("LYRICS"))
+            .map(|section| &section.text[..])
+            .filter(|lines| !lines.is_empty())
+    }
+
+    /// The links to the track on Shazam and on the streaming services
+    /// known from the hub of the track.
+    pub fn streaming_links(&self) -> StreamingLinks {
+        let hub = self.hub.as_ref();
+
+        // Apple Music links are given as options of the hub, while other
+        // services are given as providers (usually search deep links)
+
+        let apple_music = hub.and_then(|hub| {
+            hub.options
+                .iter()
+                .filter(|option| option.providername.as_deref() == Some("applemusic"))
+                .find_map(|option| first_uri(&option.actions))
+        });
+
+        let provider_uri = |provider_type: &str| {
+            hub.and_then(|hub| {
+                hub.providers
+                    .iter()
+                    .filter(|provider| provider.provider_type.as_deref() == Some(provider_type))
+                    .find_map(|provider| first_uri(&provider.actions))
+            })
+        };
+
+        StreamingLinks {
+            shazam: self.url.clone(),
+            apple_music,
+            spotify: provider_uri("SPOTIFY"),
+            deezer: provider_uri("DEEZER"),
+        }
+    }
+}
+
+/// The URI of the first action which opens one, preferring the plain
+/// "uri" actions over the ones specific to an application.
+fn first_uri(actions: &[HubAction]) -> Option<String> {
+    actions
+        .iter()
+        .filter(|action| action.uri.is_some())
+        .min_by_key(|action| action.action_type.as_deref() != Some("uri"))
+        .and_then(|action| action.uri.clone())
+}
+
+/// Links to a track on Shazam and on streaming services. Spotify and
+/// Deezer links are URIs meant to be opened by their application.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamingLinks {
+    pub shazam: Option<String>,
+    pub apple_music: Option<String>,
+    pub spotify: Option<String>,
+    pub deezer: Option<String>,
+}
+
+/// Relative time or frequency skew at which a match is considered as
+/// entirely doubtful. Shazam matches recordings which are slightly sped up
+/// or pitched, but larger skews are more likely to be wrong matches.
+const DOUBTFUL_SKEW: f64 = 0.05;
+
+/// How the recording matched the recognized track.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchDetails {
+    /// Position of the start of the recording within the track, in seconds.
+    pub offset_secs: Option<f64>,
+    pub time_skew: Option<f64>,
+    pub frequency_skew: Option<f64>,
+    /// Confidence in the match, between 0 and 1, derived from the skews
+    /// (the farther from zero, the less confident) and from the share of
+    /// the matches which agree with the first one.
+    pub confidence: f64,
+}
+
+impl ShazamResponse {
+    pub fn match_details(&self) -> Option<MatchDetails> {
+        let best_match = self.matches.first()?;
+
+        let largest_skew = best_match
+            .time_skew
+            .unwrap_or(0.0)
+            .abs()
+            .max(best_match.frequency_skew.unwrap_or(0.0).abs());
+
+        let skew_factor = (1.0 - largest_skew / DOUBTFUL_SKEW).clamp(0.0, 1.0);
+
+        let agreement_factor = self
+            .matches
+            .iter()
+            .filter(|shazam_match| shazam_match.id == best_match.id)
+            .count() as f64
+            / self.matches.len() as f64;
+
+        Some(MatchDetails {
+            offset_secs: best_match.offset,
+            time_skew: best_match.time_skew,
+            frequency_skew: best_match.frequency_skew,
+            confidence: skew_factor * agreement_factor,
+        })
+    }
+}
+
+#[cfg(test)]
+fn make_test_match(id: &str, time_skew: f64, frequency_skew: f64) -> ShazamMatch {
+    ShazamMatch {
+        id: Some(id.to_string()),
+        offset: Some(42.5),
+        time_skew: Some(time_skew),
+        frequency_skew: Some(frequency_skew),
+    }
+}
+
+#[test]
+fn test_match_details() {
+    let mut response = ShazamResponse::default();
+    assert_eq!(response.match_details(), None);
+
+    response.matches = vec![make_test_match("1", 0.0, 0.0)];
+    assert_eq!(
+        response.match_details(),
+        Some(MatchDetails {
+            offset_secs: Some(42.5),
+            time_skew: Some(0.0),
+            frequency_skew: Some(0.0),
+            confidence: 1.0,
+        })
+    );
+
+    // The confidence decreases with the largest of the skews
+
+    response.matches = vec![make_test_match("1", 0.01, -0.025)];
+    let confidence = response.match_details().unwrap().confidence;
+    assert!((confidence - 0.5).abs() < 1e-9);
+
+    response.matches = vec![make_test_match("1", DOUBTFUL_SKEW, 0.0)];
+    assert_eq!(response.match_details().unwrap().confidence, 0.0);
+
+    response.matches = vec![make_test_match("1", 0.0, -2.0 * DOUBTFUL_SKEW)];
+    assert_eq!(response.match_details().unwrap().confidence, 0.0);
+}
+
+#[test]
+fn test_match_details_disagreeing_matches() {
+    let response = ShazamResponse {
+        matches: vec![
+            make_test_match("1", 0.0, 0.0),
+            make_test_match("2", 0.0, 0.0),
+            make_test_match("1", 0.0, 0.0),
+            make_test_match("3", 0.0, 0.0),
+        ],
+        ..Default::default()
+    };
+
+    let match_details = response.match_details().unwrap();
+    assert_eq!(match_details.confidence, 0.5);
+    assert_eq!(match_details.offset_secs, Some(42.5));
+}
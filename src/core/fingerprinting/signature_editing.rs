@@ -0,0 +1,255 @@
+//! This module contains operations used to edit an existing signature
+//! without decoding audio again: cutting it to a time range, shifting it in
+//! time, trimming its silent edges and concatenating signatures together.
+//!
+//! Positions are expressed in FFT passes, as in `FrequencyPeak`. A new FFT
+//! pass is done every 128 samples, and `number_samples` is recomputed by
+//! every operation accordingly.
+
+use crate::core::fingerprinting::signature_format::{
+    DecodedSignature, FrequencyPeak, SignatureError,
+};
+
+const SAMPLES_PER_FFT_PASS: u32 = 128;
+
+impl DecodedSignature {
+    /// Number of FFT passes covered by the signature, rounded up.
+    pub fn number_fft_passes(&self) -> u32 {
+        self.number_samples.div_ceil(SAMPLES_PER_FFT_PASS)
+    }
+
+    /// Convert a position in seconds to a number of FFT passes at the
+    /// sample rate of the signature.
+    pub fn seconds_to_fft_passes(&self, seconds: f32) -> i64 {
+        (seconds as f64 * self.sample_rate_hz as f64 / SAMPLES_PER_FFT_PASS as f64).round() as i64
+    }
+
+    /// Keep only the peaks within the `[start_pass, end_pass)` range, and
+    /// make the resulting signature start at `start_pass`.
+    pub fn slice(&self, start_pass: u32, end_pass: u32) -> DecodedSignature {
+        let end_pass = end_pass.max(start_pass);
+
+        let number_samples = self
+            .number_samples
+            .min(end_pass.saturating_mul(SAMPLES_PER_FFT_PASS))
+            .saturating_sub(start_pass.saturating_mul(SAMPLES_PER_FFT_PASS));
+
+        DecodedSignature {
+            sample_rate_hz: self.sample_rate_hz,
+            number_samples,
+            frequency_band_to_sound_peaks: self.frequency_band_to_sound_peaks.clone().map(
+                |frequency_peaks| {
+                    frequency_peaks
+                        .into_iter()
+                        .filter(|frequency_peak| {
+                            (start_pass..end_pass).contains(&frequency_peak.fft_pass_number)
+                        })
+                        .map(|frequency_peak| FrequencyPeak {
+                            fft_pass_number: frequency_peak.fft_pass_number - start_pass,
+                            ..frequency_peak
+                        })
+                        .collect()
+                },
+            ),
+        }
+    }
+
+    /// Move every peak by `offset_passes` FFT passes. A positive offset
+    /// inserts silence at the start of the signature, and a negative one
+    /// drops the peaks that would end up before its start.
+    pub fn time_shift(&self, offset_passes: i64) -> DecodedSignature {
+        let shift = |position: i64| {
+            position
+                .saturating_add(offset_passes)
+                .clamp(0, u32::MAX as i64) as u32
+        };
+
+        DecodedSignature {
+            sample_rate_hz: self.sample_rate_hz,
+            number_samples: (self.number_samples as i64
+                + offset_passes.saturating_mul(SAMPLES_PER_FFT_PASS as i64))
+            .clamp(0, u32::MAX as i64) as u32,
+            frequency_band_to_sound_peaks: self.frequency_band_to_sound_peaks.clone().map(
+                |frequency_peaks| {
+                    frequency_peaks
+                        .into_iter()
+                        .filter(|frequency_peak| {
+                            (frequency_peak.fft_pass_number as i64).saturating_add(offset_passes)
+                                >= 0
+                        })
+                        .map(|frequency_peak| FrequencyPeak {
+                            fft_pass_number: shift(frequency_peak.fft_pass_number as i64),
+                            ..frequency_peak
+                        })
+                        .collect()
+                },
+            ),
+        }
+    }
+
+    /// Remove the leading and trailing FFT passes that don't contain any
+    /// peak.
+    pub fn trim(&self) -> DecodedSignature {
+        let fft_pass_numbers = || {
+            self.frequency_band_to_sound_peaks
+                .iter()
+                .flatten()
+                .map(|frequency_peak| frequency_peak.fft_pass_number)
+        };
+
+        match (fft_pass_numbers().min(), fft_pass_numbers().max()) {
+            (Some(first_pass), Some(last_pass)) => {
+                let mut signature = self.slice(first_pass, last_pass.saturating_add(1));
+
+                // Peaks found after the announced number of samples should
+                // not be cut out
+
+                signature.number_samples = (last_pass - first_pass)
+                    .saturating_add(1)
+                    .saturating_mul(SAMPLES_PER_FFT_PASS);
+
+                signature
+            }
+            _ => self.slice(0, 0),
+        }
+    }
+
+    /// Append `other` after the end of this signature, shifting its peaks
+    /// by the number of FFT passes of this signature.
+    pub fn concatenate(
+        &self,
+        other: &DecodedSignature,
+    ) -> Result<DecodedSignature, SignatureError> {
+        if self.sample_rate_hz != other.sample_rate_hz {
+            return Err(SignatureError::SampleRateMismatch {
+                expected: self.sample_rate_hz,
+                actual: other.sample_rate_hz,
+            });
+        }
+
+        let offset_passes = self.number_fft_passes();
+
+        let mut frequency_band_to_sound_peaks = self.frequency_band_to_sound_peaks.clone();
+
+        for (frequency_peaks, other_frequency_peaks) in frequency_band_to_sound_peaks
+            .iter_mut()
+            .zip(&other.frequency_band_to_sound_peaks)
+        {
+            frequency_peaks.extend(other_frequency_peaks.iter().map(|frequency_peak| {
+                FrequencyPeak {
+                    fft_pass_number: frequency_peak.fft_pass_number.saturating_add(offset_passes),
+                    ..frequency_peak.clone()
+                }
+            }));
+
+            // Peaks may have been recorded after the announced end of the
+            // first signature
+
+            frequency_peaks.sort_by_key(|frequency_peak| frequency_peak.fft_pass_number);
+        }
+
+        Ok(DecodedSignature {
+            sample_rate_hz: self.sample_rate_hz,
+            number_samples: offset_passes
+                .saturating_mul(SAMPLES_PER_FFT_PASS)
+                .saturating_add(other.number_samples),
+            frequency_band_to_sound_peaks,
+        })
+    }
+}
+
+#[cfg(test)]
+fn make_test_signature(number_samples: u32, fft_pass_numbers: &[u32]) -> DecodedSignature {
+    let mut signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    for &fft_pass_number in fft_pass_numbers {
+        signature.frequency_band_to_sound_peaks[1].push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 10000,
+            corrected_peak_frequency_bin: 6000,
+        });
+    }
+
+    signature
+}
+
+#[cfg(test)]
+fn fft_pass_numbers(signature: &DecodedSignature) -> Vec<u32> {
+    signature.frequency_band_to_sound_peaks[1]
+        .iter()
+        .map(|frequency_peak| frequency_peak.fft_pass_number)
+        .collect()
+}
+
+#[test]
+fn test_signature_slice() {
+    let signature = make_test_signature(128 * 100, &[5, 20, 50, 99]);
+
+    let slice = signature.slice(20, 60);
+    assert_eq!(fft_pass_numbers(&slice), vec![0, 30]);
+    assert_eq!(slice.number_samples, 128 * 40);
+
+    // Ranges beyond the end of the signature are cut to it
+
+    let slice = signature.slice(90, 200);
+    assert_eq!(fft_pass_numbers(&slice), vec![9]);
+    assert_eq!(slice.number_samples, 128 * 10);
+
+    let slice = signature.slice(60, 20);
+    assert!(fft_pass_numbers(&slice).is_empty());
+    assert_eq!(slice.number_samples, 0);
+}
+
+#[test]
+fn test_signature_time_shift() {
+    let signature = make_test_signature(128 * 100, &[5, 20, 50]);
+
+    let shifted = signature.time_shift(10);
+    assert_eq!(fft_pass_numbers(&shifted), vec![15, 30, 60]);
+    assert_eq!(shifted.number_samples, 128 * 110);
+
+    let shifted = signature.time_shift(-10);
+    assert_eq!(fft_pass_numbers(&shifted), vec![10, 40]);
+    assert_eq!(shifted.number_samples, 128 * 90);
+
+    let shifted = signature.time_shift(i64::MIN);
+    assert!(fft_pass_numbers(&shifted).is_empty());
+    assert_eq!(shifted.number_samples, 0);
+}
+
+#[test]
+fn test_signature_trim() {
+    let trimmed = make_test_signature(128 * 100, &[5, 20, 50]).trim();
+    assert_eq!(fft_pass_numbers(&trimmed), vec![0, 15, 45]);
+    assert_eq!(trimmed.number_samples, 128 * 46);
+
+    // Extreme positions must not overflow the number of samples
+
+    let trimmed = make_test_signature(u32::MAX, &[0, u32::MAX - 1]).trim();
+    assert_eq!(fft_pass_numbers(&trimmed), vec![0, u32::MAX - 1]);
+    assert_eq!(trimmed.number_samples, u32::MAX);
+
+    let trimmed = make_test_signature(128 * 100, &[]).trim();
+    assert_eq!(trimmed.number_samples, 0);
+}
+
+#[test]
+fn test_signature_concatenate() {
+    let first = make_test_signature(128 * 10 + 1, &[2, 12]);
+    let second = make_test_signature(128 * 5, &[0, 3]);
+
+    let concatenated = first.concatenate(&second).unwrap();
+    assert_eq!(fft_pass_numbers(&concatenated), vec![2, 11, 12, 14]);
+    assert_eq!(concatenated.number_samples, 128 * 11 + 128 * 5);
+
+    let mut other_rate = second.clone();
+    other_rate.sample_rate_hz = 44100;
+    assert!(matches!(
+        first.concatenate(&other_rate),
+        Err(SignatureError::SampleRateMismatch { .. })
+    ));
+}
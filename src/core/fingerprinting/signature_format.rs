@@ -1,51 +1,222 @@
+use crate::utils::internationalization::gettext;
 use base64::Engine;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use crc32fast::Hasher;
-use gettextrs::gettext;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::io::{Cursor, Seek, SeekFrom, Write};
 
 const DATA_URI_PREFIX: &str = "data:audio/vnd.shazam.sig;base64,";
 
+/// An error encountered while decoding or encoding a Shazam signature.
+#[derive(Debug, Clone)]
+pub enum SignatureError {
+    /// The data URI does not have the expected prefix, or its contents are
+    /// not valid base64.
+    InvalidDataUri,
+    /// One of the fixed magic values of the header does not match.
+    BadMagic,
+    /// The CRC-32 checksum stored in the header does not match the data.
+    CrcMismatch { expected: u32, actual: u32 },
+    /// The data is shorter than announced by the header.
+    Truncated,
+    /// A frequency band identifier is not one of the four known bands.
+    UnknownBand(u32),
+    /// The sample rate (or sample rate identifier, when decoding) can't be
+    /// represented in a Shazam signature.
+    UnsupportedSampleRate(u32),
+    /// Frequency peaks are not sorted by FFT pass number.
+    UnorderedPeaks,
+    /// Two signatures to be combined don't have the same sample rate.
+    SampleRateMismatch { expected: u32, actual: u32 },
+    /// The number of samples is too large to be stored in the header.
+    TooManySamples(u32),
+}
+
+impl SignatureError {
+    /// Process exit code used when the command-line interface fails
+    /// because of this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SignatureError::InvalidDataUri => 3,
+            SignatureError::BadMagic => 4,
+            SignatureError::CrcMismatch { .. } => 5,
+            SignatureError::Truncated => 6,
+            SignatureError::UnknownBand(_) => 7,
+            SignatureError::UnsupportedSampleRate(_) => 8,
+            SignatureError::UnorderedPeaks => 9,
+            SignatureError::SampleRateMismatch { .. } => 10,
+            SignatureError::TooManySamples(_) => 11,
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SignatureError::InvalidDataUri => {
+                write!(
+                    formatter,
+                    "{}",
+                    gettext("Invalid Shazam signature data URI")
+                )
+            }
+            SignatureError::BadMagic => {
+                write!(
+                    formatter,
+                    "{}",
+                    gettext("Invalid magic number in decoded Shazam packet")
+                )
+            }
+            SignatureError::CrcMismatch { expected, actual } => {
+                write!(
+                    formatter,
+                    "{} ({:08x} != {:08x})",
+                    gettext("Invalid checksum in decoded Shazam packet"),
+                    expected,
+                    actual
+                )
+            }
+            SignatureError::Truncated => {
+                write!(formatter, "{}", gettext("Truncated Shazam packet"))
+            }
+            SignatureError::UnknownBand(frequency_band_id) => {
+                write!(
+                    formatter,
+                    "{} ({:#x})",
+                    gettext("Invalid frequency band in decoded Shazam packet"),
+                    frequency_band_id
+                )
+            }
+            SignatureError::UnsupportedSampleRate(sample_rate) => {
+                write!(
+                    formatter,
+                    "{} ({})",
+                    gettext("Unsupported sample rate for a Shazam packet"),
+                    sample_rate
+                )
+            }
+            SignatureError::UnorderedPeaks => {
+                write!(
+                    formatter,
+                    "{}",
+                    gettext("Unordered frequency peaks passed when encoding Shazam packet")
+                )
+            }
+            SignatureError::SampleRateMismatch { expected, actual } => {
+                write!(
+                    formatter,
+                    "{} ({} != {})",
+                    gettext("Mismatching sample rates between Shazam packets"),
+                    expected,
+                    actual
+                )
+            }
+            SignatureError::TooManySamples(number_samples) => {
+                write!(
+                    formatter,
+                    "{} ({})",
+                    gettext("Too many samples for a Shazam packet"),
+                    number_samples
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+// Reading from or writing to an in-memory cursor may only fail when reaching
+// the end of the data
+
+impl From<std::io::Error> for SignatureError {
+    fn from(_error: std::io::Error) -> Self {
+        SignatureError::Truncated
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FrequencyPeak {
     pub fft_pass_number: u32,
     pub peak_magnitude: u16,
     pub corrected_peak_frequency_bin: u16,
 }
 
-#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+impl FrequencyPeak {
+    pub fn get_frequency_hz(&self, sample_rate_hz: u32) -> f32 {
+        // Convert back a FFT bin to a frequency, given 1024 useful bins and
+        // the multiplication by 64 made before storing the information
+
+        self.corrected_peak_frequency_bin as f32 * (sample_rate_hz as f32 / 2.0 / 1024.0 / 64.0)
+    }
+
+    pub fn get_amplitude_pcm(&self) -> f32 {
+        // Not sure about this calculation but gives small enough numbers
+
+        ((((self.peak_magnitude as f32 - 6144.0) / 1477.3).exp() * (1 << 17) as f32 / 2.0).sqrt())
+            / 1024.0
+    }
+
+    pub fn get_seconds(&self, sample_rate_hz: u32) -> f32 {
+        // New FFT bins are emitted every 128 samples
+
+        (self.fft_pass_number * 128) as f32 / sample_rate_hz as f32
+    }
+}
+
+#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
 pub enum FrequencyBand {
+    #[serde(rename = "250_520")]
     _250_520 = 0,
+    #[serde(rename = "520_1450")]
     _520_1450 = 1,
+    #[serde(rename = "1450_3500")]
     _1450_3500 = 2,
+    #[serde(rename = "3500_5500")]
     _3500_5500 = 3,
 }
 
-struct RawSignatureHeader {
-    magic1: u32,                                  // Fixed 0xcafe2580 - 80 25 fe ca
-    crc32: u32, // CRC-32 for all of the following (so excluding these first 8 bytes)
-    size_minus_header: u32, // Total size of the message, minus the size of the current header (which is 48 bytes)
-    magic2: u32,            // Fixed 0x94119c00 - 00 9c 11 94
-    _void1: [u32; 3],       // Void
-    shifted_sample_rate_id: u32, // A member of SampleRate (usually 3 for 16000 Hz), left-shifted by 27 (usually giving 0x18000000 - 00 00 00 18)
-    _void2: [u32; 2],            // Void, or maybe used only in "rolling window" mode?
-    number_samples_plus_divided_sample_rate: u32, // int(number_of_samples + sample_rate * 0.24) - As the sample rate is known thanks to the field above, it can be inferred and substracted so that we obtain the number of samples, and from the number of samples and sample rate we can obtain the length of the recording
-    _fixed_value: u32, // Calculated as ((15 << 19) + 0x40000) - 0x7c0000 or 00 00 7c 00 - seems pretty constant, may be different in the "SigType.STREAMING" mode
+impl FrequencyBand {
+    pub const ALL: [FrequencyBand; 4] = [
+        FrequencyBand::_250_520,
+        FrequencyBand::_520_1450,
+        FrequencyBand::_1450_3500,
+        FrequencyBand::_3500_5500,
+    ];
+
+    /// Name of the band, as used in the JSON representation of signatures.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrequencyBand::_250_520 => "250_520",
+            FrequencyBand::_520_1450 => "520_1450",
+            FrequencyBand::_1450_3500 => "1450_3500",
+            FrequencyBand::_3500_5500 => "3500_5500",
+        }
+    }
 }
 
-pub struct DecodedSignature {
-    pub sample_rate_hz: u32,
-    pub number_samples: u32,
-    pub frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4],
+pub struct RawSignatureHeader {
+    pub magic1: u32,                                  // Fixed 0xcafe2580 - 80 25 fe ca
+    pub crc32: u32, // CRC-32 for all of the following (so excluding these first 8 bytes)
+    pub size_minus_header: u32, // Total size of the message, minus the size of the current header (which is 48 bytes)
+    pub magic2: u32,            // Fixed 0x94119c00 - 00 9c 11 94
+    pub _void1: [u32; 3],       // Void
+    pub shifted_sample_rate_id: u32, // A member of SampleRate (usually 3 for 16000 Hz), left-shifted by 27 (usually giving 0x18000000 - 00 00 00 18)
+    pub _void2: [u32; 2],            // Void, or maybe used only in "rolling window" mode?
+    pub number_samples_plus_divided_sample_rate: u32, // int(number_of_samples + sample_rate * 0.24) - As the sample rate is known thanks to the field above, it can be inferred and substracted so that we obtain the number of samples, and from the number of samples and sample rate we can obtain the length of the recording
+    pub fixed_value: u32, // Calculated as ((15 << 19) + 0x40000) - 0x7c0000 or 00 00 7c 00 - seems pretty constant, may be different in the "SigType.STREAMING" mode
 }
 
-impl DecodedSignature {
-    pub fn decode_from_binary(data: &[u8]) -> Result<Self, Box<dyn Error>> {
-        assert!(data.len() > 48 + 8);
-
+impl RawSignatureHeader {
+    /// Read the 48-byte header at the start of a binary signature, without
+    /// checking any of its fields.
+    pub fn decode_from_binary(data: &[u8]) -> Result<Self, SignatureError> {
         let mut cursor = Cursor::new(data);
 
-        let header = RawSignatureHeader {
+        Ok(RawSignatureHeader {
             magic1: cursor.read_u32::<LittleEndian>()?,
             crc32: cursor.read_u32::<LittleEndian>()?,
             size_minus_header: cursor.read_u32::<LittleEndian>()?,
@@ -61,15 +232,124 @@ impl DecodedSignature {
                 cursor.read_u32::<LittleEndian>()?,
             ],
             number_samples_plus_divided_sample_rate: cursor.read_u32::<LittleEndian>()?,
-            _fixed_value: cursor.read_u32::<LittleEndian>()?,
-        };
+            fixed_value: cursor.read_u32::<LittleEndian>()?,
+        })
+    }
 
+    /// Compute the CRC-32 checksum of the data following the checksum
+    /// field, to be compared with `crc32`.
+    pub fn compute_crc32(data: &[u8]) -> u32 {
         let mut hasher = Hasher::new();
-        hasher.update(&data[8..]);
-        assert_eq!(header.magic1, 0xcafe2580);
-        assert_eq!(header.size_minus_header as usize, data.len() - 48);
-        assert_eq!(header.crc32, hasher.finalize());
-        assert_eq!(header.magic2, 0x94119c00);
+        hasher.update(&data[8.min(data.len())..]);
+        hasher.finalize()
+    }
+}
+
+/// A decoded Shazam signature.
+///
+/// Its serde representation (used for the JSON and TOML formats) is the
+/// same as the one of the Python version's `fingerprint_to_json.py`
+/// utility:
+///
+/// ```json
+/// {
+///     "sample_rate_hz": 16000,
+///     "number_samples": 192000,
+///     "frequency_band_to_peaks": {
+///         "250_520": [
+///             {
+///                 "fft_pass_number": 12,
+///                 "peak_magnitude": 21250,
+///                 "corrected_peak_frequency_bin": 2306
+///             }
+///         ],
+///         "520_1450": [],
+///         "1450_3500": [],
+///         "3500_5500": []
+///     }
+/// }
+/// ```
+///
+/// Bands without peaks may be omitted, and fields whose name starts with an
+/// underscore (such as `_seconds` or `_frequency_hz`, which are computed
+/// for readability) are ignored when deserializing. Peaks must be sorted by
+/// FFT pass number within each band.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DecodedSignature {
+    pub sample_rate_hz: u32,
+    pub number_samples: u32,
+    #[serde(rename = "frequency_band_to_peaks", with = "frequency_band_map")]
+    pub frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4],
+}
+
+/// Represent the peaks of each band as a map keyed by band name, rather
+/// than as an array indexed by band.
+mod frequency_band_map {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        frequency_band_to_sound_peaks: &[Vec<FrequencyPeak>; 4],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        FrequencyBand::ALL
+            .iter()
+            .zip(frequency_band_to_sound_peaks)
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[Vec<FrequencyPeak>; 4], D::Error> {
+        let mut frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4] = Default::default();
+
+        for (frequency_band, frequency_peaks) in
+            BTreeMap::<FrequencyBand, Vec<FrequencyPeak>>::deserialize(deserializer)?
+        {
+            frequency_band_to_sound_peaks[frequency_band as usize] = frequency_peaks;
+        }
+
+        Ok(frequency_band_to_sound_peaks)
+    }
+}
+
+impl DecodedSignature {
+    pub fn decode_from_binary(data: &[u8]) -> Result<Self, SignatureError> {
+        DecodedSignature::decode_from_binary_checked(data, true)
+    }
+
+    /// Decode a binary signature even if its CRC-32 checksum does not
+    /// match (e.g. for inspecting a corrupted signature).
+    pub fn decode_from_binary_ignoring_crc(data: &[u8]) -> Result<Self, SignatureError> {
+        DecodedSignature::decode_from_binary_checked(data, false)
+    }
+
+    fn decode_from_binary_checked(data: &[u8], check_crc: bool) -> Result<Self, SignatureError> {
+        if data.len() < 48 + 8 {
+            return Err(SignatureError::Truncated);
+        }
+
+        let header = RawSignatureHeader::decode_from_binary(data)?;
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(48);
+
+        if header.magic1 != 0xcafe2580 || header.magic2 != 0x94119c00 {
+            return Err(SignatureError::BadMagic);
+        }
+
+        if header.size_minus_header as usize != data.len() - 48 {
+            return Err(SignatureError::Truncated);
+        }
+
+        let crc32 = RawSignatureHeader::compute_crc32(data);
+
+        if check_crc && header.crc32 != crc32 {
+            return Err(SignatureError::CrcMismatch {
+                expected: header.crc32,
+                actual: crc32,
+            });
+        }
 
         let sample_rate_hz: u32 = match header.shifted_sample_rate_id >> 27 {
             1 => 8000,
@@ -78,24 +358,26 @@ impl DecodedSignature {
             4 => 32000,
             5 => 44100,
             6 => 48000,
-            _ => {
-                panic!(
-                    "{}",
-                    gettext("Invalid sample rate in decoded Shazam packet")
-                );
+            sample_rate_id => {
+                return Err(SignatureError::UnsupportedSampleRate(sample_rate_id));
             }
         };
 
-        let number_samples: u32 =
-            header.number_samples_plus_divided_sample_rate - (sample_rate_hz as f32 * 0.24) as u32;
+        let number_samples: u32 = header
+            .number_samples_plus_divided_sample_rate
+            .saturating_sub((sample_rate_hz as f32 * 0.24) as u32);
 
         // Read the type-length-value sequence that follows the header
 
         // The first chunk is fixed and has no value, but instead just repeats
         // the length of the message size minus the header:
 
-        assert_eq!(cursor.read_u32::<LittleEndian>()?, 0x40000000);
-        assert_eq!(cursor.read_u32::<LittleEndian>()? as usize, data.len() - 48);
+        if cursor.read_u32::<LittleEndian>()? != 0x40000000 {
+            return Err(SignatureError::BadMagic);
+        }
+        if cursor.read_u32::<LittleEndian>()? as usize != data.len() - 48 {
+            return Err(SignatureError::Truncated);
+        }
 
         // Then, lists of frequency peaks for respective bands follow
 
@@ -107,23 +389,24 @@ impl DecodedSignature {
 
             let frequency_peaks_padding = (4 - frequency_peaks_size % 4) % 4;
 
-            let mut frequency_peaks_cursor = Cursor::new(
-                &data[cursor.position() as usize
-                    ..(cursor.position() as u32 + frequency_peaks_size) as usize],
-            );
+            let frequency_peaks_data = data
+                .get(
+                    cursor.position() as usize
+                        ..cursor.position() as usize + frequency_peaks_size as usize,
+                )
+                .ok_or(SignatureError::Truncated)?;
+
+            let mut frequency_peaks_cursor = Cursor::new(frequency_peaks_data);
 
             // Decode frequency peaks
 
-            let frequency_band = match frequency_band_id - 0x60030040 {
+            let frequency_band = match frequency_band_id.wrapping_sub(0x60030040) {
                 0 => FrequencyBand::_250_520,
                 1 => FrequencyBand::_520_1450,
                 2 => FrequencyBand::_1450_3500,
                 3 => FrequencyBand::_3500_5500,
                 _ => {
-                    panic!(
-                        "{}",
-                        gettext("Invalid frequency band in decoded Shazam packet")
-                    );
+                    return Err(SignatureError::UnknownBand(frequency_band_id));
                 }
             };
 
@@ -137,7 +420,7 @@ impl DecodedSignature {
                         fft_pass_number = frequency_peaks_cursor.read_u32::<LittleEndian>()?;
                     }
                     _ => {
-                        fft_pass_number += fft_pass_offset as u32;
+                        fft_pass_number = fft_pass_number.wrapping_add(fft_pass_offset as u32);
 
                         frequency_band_to_sound_peaks[frequency_band as usize].push(
                             FrequencyPeak {
@@ -166,15 +449,48 @@ impl DecodedSignature {
         })
     }
 
-    pub fn decode_from_uri(uri: &str) -> Result<Self, Box<dyn Error>> {
-        assert!(uri.starts_with(DATA_URI_PREFIX));
+    /// Read a signature passed on the command line: either a data URI, or
+    /// the path of a file containing a binary signature, a data URI, or
+    /// the JSON or TOML representation of a signature.
+    pub fn decode_from_input(input: &str) -> Result<Self, Box<dyn Error>> {
+        if input.starts_with("data:") {
+            return Ok(DecodedSignature::decode_from_uri(input)?);
+        }
+
+        let contents = std::fs::read(input)?;
+
+        if contents.starts_with(&0xcafe2580_u32.to_le_bytes()) {
+            return Ok(DecodedSignature::decode_from_binary(&contents)?);
+        }
+
+        let text = String::from_utf8(contents)?;
+        let text = text.trim();
 
-        DecodedSignature::decode_from_binary(
-            &base64::prelude::BASE64_STANDARD.decode(&uri[DATA_URI_PREFIX.len()..])?,
-        )
+        if text.starts_with("data:") {
+            Ok(DecodedSignature::decode_from_uri(text)?)
+        } else if text.starts_with('{') {
+            Ok(serde_json::from_str(text)?)
+        } else {
+            Ok(toml::from_str(text)?)
+        }
+    }
+
+    pub fn decode_from_uri(uri: &str) -> Result<Self, SignatureError> {
+        DecodedSignature::decode_from_binary(&DecodedSignature::decode_uri_to_binary(uri)?)
+    }
+
+    /// Extract the binary signature contained in a data URI.
+    pub fn decode_uri_to_binary(uri: &str) -> Result<Vec<u8>, SignatureError> {
+        let base64_data = uri
+            .strip_prefix(DATA_URI_PREFIX)
+            .ok_or(SignatureError::InvalidDataUri)?;
+
+        base64::prelude::BASE64_STANDARD
+            .decode(base64_data)
+            .map_err(|_| SignatureError::InvalidDataUri)
     }
 
-    pub fn encode_to_binary(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn encode_to_binary(&self) -> Result<Vec<u8>, SignatureError> {
         let mut cursor = Cursor::new(vec![]);
 
         // Please see the RawSignatureHeader structure definition above for
@@ -195,18 +511,17 @@ impl DecodedSignature {
                 32000 => 4,
                 44100 => 5,
                 48000 => 6,
-                _ => {
-                    panic!(
-                        "{}",
-                        gettext("Invalid sample rate passed when encoding Shazam packet")
-                    );
+                sample_rate_hz => {
+                    return Err(SignatureError::UnsupportedSampleRate(sample_rate_hz));
                 }
             } << 27,
         )?; // shifted_sample_rate_id
         cursor.write_u32::<LittleEndian>(0)?; // void2
         cursor.write_u32::<LittleEndian>(0)?;
         cursor.write_u32::<LittleEndian>(
-            self.number_samples + (self.sample_rate_hz as f32 * 0.24) as u32,
+            self.number_samples
+                .checked_add((self.sample_rate_hz as f32 * 0.24) as u32)
+                .ok_or(SignatureError::TooManySamples(self.number_samples))?,
         )?; // number_samples_plus_divided_sample_rate
         cursor.write_u32::<LittleEndian>((15 << 19) + 0x40000)?; // fixed_value
 
@@ -224,7 +539,9 @@ impl DecodedSignature {
             let mut fft_pass_number = 0;
 
             for frequency_peak in frequency_peaks {
-                assert!(frequency_peak.fft_pass_number >= fft_pass_number);
+                if frequency_peak.fft_pass_number < fft_pass_number {
+                    return Err(SignatureError::UnorderedPeaks);
+                }
 
                 if frequency_peak.fft_pass_number - fft_pass_number >= 255 {
                     peaks_cursor.write_u8(0xff)?;
@@ -268,7 +585,7 @@ impl DecodedSignature {
         Ok(cursor.into_inner())
     }
 
-    pub fn encode_to_uri(&self) -> Result<String, Box<dyn Error>> {
+    pub fn encode_to_uri(&self) -> Result<String, SignatureError> {
         Ok(format!(
             "{}{}",
             DATA_URI_PREFIX,
@@ -276,3 +593,206 @@ impl DecodedSignature {
         ))
     }
 }
+
+#[test]
+fn test_signature_serde_round_trip() {
+    let mut signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 16000 * 12,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    // Include gaps of more than 255 FFT passes, which are encoded
+    // differently in the binary format
+
+    for fft_pass_number in [3, 40, 41, 400, 1450] {
+        signature.frequency_band_to_sound_peaks[1].push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 9000 + fft_pass_number as u16,
+            corrected_peak_frequency_bin: 6000 + fft_pass_number as u16,
+        });
+    }
+    signature.frequency_band_to_sound_peaks[3].push(FrequencyPeak {
+        fft_pass_number: 12,
+        peak_magnitude: 12000,
+        corrected_peak_frequency_bin: 40000,
+    });
+
+    let binary = signature.encode_to_binary().unwrap();
+
+    let json =
+        serde_json::to_string(&DecodedSignature::decode_from_binary(&binary).unwrap()).unwrap();
+    let from_json: DecodedSignature = serde_json::from_str(&json).unwrap();
+    assert_eq!(from_json.encode_to_binary().unwrap(), binary);
+
+    let from_toml: DecodedSignature =
+        toml::from_str(&toml::to_string(&from_json).unwrap()).unwrap();
+    assert_eq!(from_toml.encode_to_binary().unwrap(), binary);
+}
+
+#[test]
+fn test_signature_encoding_too_many_samples() {
+    let signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: u32::MAX - 100,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    assert!(matches!(
+        signature.encode_to_binary(),
+        Err(SignatureError::TooManySamples(_))
+    ));
+}
+
+/// Encode a signature with peaks in two bands, for corrupting it.
+#[cfg(test)]
+fn make_test_binary() -> Vec<u8> {
+    let mut signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 16000 * 12,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    for fft_pass_number in [3, 40, 400] {
+        signature.frequency_band_to_sound_peaks[1].push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 9000,
+            corrected_peak_frequency_bin: 6000,
+        });
+    }
+    signature.frequency_band_to_sound_peaks[3].push(FrequencyPeak {
+        fft_pass_number: 12,
+        peak_magnitude: 12000,
+        corrected_peak_frequency_bin: 40000,
+    });
+
+    signature.encode_to_binary().unwrap()
+}
+
+/// Overwrite a little-endian field of a binary signature, and update its
+/// checksum so that only the overwritten field is invalid.
+#[cfg(test)]
+fn overwrite_field(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    let crc32 = RawSignatureHeader::compute_crc32(data);
+    data[4..8].copy_from_slice(&crc32.to_le_bytes());
+}
+
+#[test]
+fn test_signature_decoding_errors() {
+    let binary = make_test_binary();
+    assert!(DecodedSignature::decode_from_binary(&binary).is_ok());
+
+    let mut data = binary.clone();
+    data[0] ^= 0xff;
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::BadMagic)
+    ));
+
+    let mut data = binary.clone();
+    overwrite_field(&mut data, 48, 0x40000001);
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::BadMagic)
+    ));
+
+    let mut data = binary.clone();
+    *data.last_mut().unwrap() ^= 0xff;
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::CrcMismatch { .. })
+    ));
+    assert!(DecodedSignature::decode_from_binary_ignoring_crc(&data).is_ok());
+
+    let mut data = binary.clone();
+    overwrite_field(&mut data, 28, 7 << 27);
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::UnsupportedSampleRate(7))
+    ));
+
+    // The first band follows the header and the fixed chunk
+
+    let mut data = binary.clone();
+    overwrite_field(&mut data, 56, 0x60030050);
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::UnknownBand(0x60030050))
+    ));
+}
+
+#[test]
+fn test_signature_decoding_truncated() {
+    let binary = make_test_binary();
+
+    for length in [0, 8, 47, 55] {
+        assert!(matches!(
+            DecodedSignature::decode_from_binary(&binary[..length]),
+            Err(SignatureError::Truncated)
+        ));
+    }
+
+    // Data shorter than announced by the header
+
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&binary[..binary.len() - 4]),
+        Err(SignatureError::Truncated)
+    ));
+
+    // A peak list cut off in the middle of the last band, with the sizes
+    // of the header matching the shortened data
+
+    let mut data = binary[..binary.len() - 6].to_vec();
+    let size_minus_header = (data.len() - 48) as u32;
+    overwrite_field(&mut data, 8, size_minus_header);
+    overwrite_field(&mut data, 52, size_minus_header);
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::Truncated)
+    ));
+
+    // A band announcing more peak bytes than the peaks it contains
+
+    let mut data = binary.clone();
+    let first_band_size = u32::from_le_bytes(data[60..64].try_into().unwrap());
+    overwrite_field(&mut data, 60, first_band_size - 2);
+    assert!(matches!(
+        DecodedSignature::decode_from_binary(&data),
+        Err(SignatureError::Truncated)
+    ));
+}
+
+#[test]
+fn test_signature_decoding_data_uri() {
+    let binary = make_test_binary();
+    let uri = format!(
+        "{}{}",
+        DATA_URI_PREFIX,
+        base64::prelude::BASE64_STANDARD.encode(&binary)
+    );
+    assert!(DecodedSignature::decode_from_uri(&uri).is_ok());
+
+    for uri in [
+        "",
+        "data:text/plain;base64,AAAA",
+        "data:audio/vnd.shazam.sig;base64,not base64!",
+    ] {
+        assert!(matches!(
+            DecodedSignature::decode_from_uri(uri),
+            Err(SignatureError::InvalidDataUri)
+        ));
+    }
+
+    // Errors of the binary signature are reported as such
+
+    let uri = format!(
+        "{}{}",
+        DATA_URI_PREFIX,
+        base64::prelude::BASE64_STANDARD.encode(&binary[..binary.len() / 2])
+    );
+    assert!(matches!(
+        DecodedSignature::decode_from_uri(&uri),
+        Err(SignatureError::Truncated)
+    ));
+}
@@ -0,0 +1,326 @@
+//! This module contains code used to inspect the contents of a Shazam
+//! signature for debugging purposes: header fields, checksum validity,
+//! frequency peaks and a rendering of these peaks as a constellation plot.
+
+use crate::utils::internationalization::gettext;
+use image::{Rgb, RgbImage};
+use serde_json::{Value, json};
+use std::error::Error;
+use std::path::Path;
+
+use crate::core::fingerprinting::signature_format::{
+    DecodedSignature, FrequencyBand, RawSignatureHeader, SignatureError,
+};
+
+/// Maximal width of a constellation plot, in pixels. Longer signatures are
+/// horizontally scaled down.
+const MAX_PLOT_WIDTH: u32 = 4096;
+
+/// Height of a constellation plot, in pixels. Each row covers two of the
+/// 1024 FFT bins (so that the plot spans 0 to 8 KHz at 16 KHz).
+const PLOT_HEIGHT: u32 = 512;
+
+const BAND_COLORS: [Rgb<u8>; 4] = [
+    Rgb([255, 90, 90]),
+    Rgb([255, 200, 60]),
+    Rgb([90, 220, 120]),
+    Rgb([90, 160, 255]),
+];
+
+pub struct SignatureInspection {
+    pub header: RawSignatureHeader,
+    pub computed_crc32: u32,
+    pub data_size: usize,
+    /// The decoded signature (ignoring the CRC-32 checksum), or the reason
+    /// why it could not be decoded.
+    pub signature: Result<DecodedSignature, SignatureError>,
+}
+
+impl SignatureInspection {
+    /// Read a data-URI signature, or a binary signature file (or a file
+    /// containing a data-URI signature).
+    pub fn from_input(input: &str) -> Result<Self, Box<dyn Error>> {
+        let data = if input.starts_with("data:") {
+            DecodedSignature::decode_uri_to_binary(input)?
+        } else {
+            let contents = std::fs::read(input)?;
+
+            if contents.starts_with(b"data:") {
+                DecodedSignature::decode_uri_to_binary(String::from_utf8_lossy(&contents).trim())?
+            } else {
+                contents
+            }
+        };
+
+        Ok(SignatureInspection::from_binary(&data)?)
+    }
+
+    pub fn from_binary(data: &[u8]) -> Result<Self, SignatureError> {
+        Ok(SignatureInspection {
+            header: RawSignatureHeader::decode_from_binary(data)?,
+            computed_crc32: RawSignatureHeader::compute_crc32(data),
+            data_size: data.len(),
+            signature: DecodedSignature::decode_from_binary_ignoring_crc(data),
+        })
+    }
+
+    pub fn is_crc32_valid(&self) -> bool {
+        self.header.crc32 == self.computed_crc32
+    }
+
+    pub fn print_report(&self) {
+        let header = &self.header;
+
+        println!(
+            "{} {:#010x}, {:#010x} ({})",
+            gettext("Magic numbers:"),
+            header.magic1,
+            header.magic2,
+            if header.magic1 == 0xcafe2580 && header.magic2 == 0x94119c00 {
+                gettext("valid")
+            } else {
+                gettext("invalid")
+            }
+        );
+        println!(
+            "{} {:#010x}, {} {:#010x} ({})",
+            gettext("CRC-32:"),
+            header.crc32,
+            gettext("computed:"),
+            self.computed_crc32,
+            if self.is_crc32_valid() {
+                gettext("valid")
+            } else {
+                gettext("invalid")
+            }
+        );
+        println!(
+            "{} {} ({} {})",
+            gettext("Size minus header:"),
+            header.size_minus_header,
+            gettext("actual:"),
+            self.data_size.saturating_sub(48)
+        );
+        println!(
+            "{} {:#010x}",
+            gettext("Shifted sample rate ID:"),
+            header.shifted_sample_rate_id
+        );
+        println!(
+            "{} {}",
+            gettext("Number of samples plus divided sample rate:"),
+            header.number_samples_plus_divided_sample_rate
+        );
+        println!("{} {:#010x}", gettext("Fixed value:"), header.fixed_value);
+
+        match &self.signature {
+            Ok(signature) => {
+                println!(
+                    "{} {} Hz",
+                    gettext("Sample rate:"),
+                    signature.sample_rate_hz
+                );
+                println!(
+                    "{} {}",
+                    gettext("Number of samples:"),
+                    signature.number_samples
+                );
+                println!(
+                    "{} {:.3} s",
+                    gettext("Duration:"),
+                    signature.number_samples as f32 / signature.sample_rate_hz as f32
+                );
+                println!("{}", gettext("Frequency peaks per band:"));
+                for frequency_band in FrequencyBand::ALL {
+                    println!(
+                        "    {} Hz: {}",
+                        frequency_band.name().replace('_', "-"),
+                        signature.frequency_band_to_sound_peaks[frequency_band as usize].len()
+                    );
+                }
+            }
+            Err(error) => {
+                println!(
+                    "{} {}",
+                    gettext("Could not decode the frequency peaks:"),
+                    error
+                );
+            }
+        }
+    }
+
+    pub fn encode_to_json(&self) -> Value {
+        let header = &self.header;
+
+        let mut json_object = json!({
+            "header": {
+                "magic1": header.magic1,
+                "magic2": header.magic2,
+                "crc32": header.crc32,
+                "computed_crc32": self.computed_crc32,
+                "crc32_valid": self.is_crc32_valid(),
+                "size_minus_header": header.size_minus_header,
+                "shifted_sample_rate_id": header.shifted_sample_rate_id,
+                "number_samples_plus_divided_sample_rate": header.number_samples_plus_divided_sample_rate,
+                "fixed_value": header.fixed_value,
+            },
+        });
+
+        match &self.signature {
+            Ok(signature) => {
+                json_object["signature"] = encode_peaks_to_json(signature);
+            }
+            Err(error) => {
+                json_object["error"] = Value::String(error.to_string());
+            }
+        }
+
+        json_object
+    }
+}
+
+/// Represent a signature in the same JSON format as the Python version's
+/// `fingerprint_to_json.py` utility.
+pub fn encode_peaks_to_json(signature: &DecodedSignature) -> Value {
+    let mut frequency_band_to_peaks = serde_json::Map::new();
+
+    for frequency_band in FrequencyBand::ALL {
+        frequency_band_to_peaks.insert(
+            frequency_band.name().to_string(),
+            signature.frequency_band_to_sound_peaks[frequency_band as usize]
+                .iter()
+                .map(|frequency_peak| {
+                    json!({
+                        "fft_pass_number": frequency_peak.fft_pass_number,
+                        "peak_magnitude": frequency_peak.peak_magnitude,
+                        "corrected_peak_frequency_bin": frequency_peak.corrected_peak_frequency_bin,
+                        "_frequency_hz": frequency_peak.get_frequency_hz(signature.sample_rate_hz),
+                        "_amplitude_pcm": frequency_peak.get_amplitude_pcm(),
+                        "_seconds": frequency_peak.get_seconds(signature.sample_rate_hz),
+                    })
+                })
+                .collect(),
+        );
+    }
+
+    json!({
+        "sample_rate_hz": signature.sample_rate_hz,
+        "number_samples": signature.number_samples,
+        "_seconds": signature.number_samples as f32 / signature.sample_rate_hz as f32,
+        "frequency_band_to_peaks": frequency_band_to_peaks,
+    })
+}
+
+/// Write the frequency peaks of a signature in the CSV format, one peak
+/// per line.
+pub fn write_peaks_csv(
+    signature: &DecodedSignature,
+    writer: impl std::io::Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    csv_writer.write_record([
+        "frequency_band",
+        "fft_pass_number",
+        "peak_magnitude",
+        "corrected_peak_frequency_bin",
+        "frequency_hz",
+        "amplitude_pcm",
+        "seconds",
+    ])?;
+
+    for frequency_band in FrequencyBand::ALL {
+        for frequency_peak in &signature.frequency_band_to_sound_peaks[frequency_band as usize] {
+            csv_writer.write_record([
+                frequency_band.name().to_string(),
+                frequency_peak.fft_pass_number.to_string(),
+                frequency_peak.peak_magnitude.to_string(),
+                frequency_peak.corrected_peak_frequency_bin.to_string(),
+                frequency_peak
+                    .get_frequency_hz(signature.sample_rate_hz)
+                    .to_string(),
+                frequency_peak.get_amplitude_pcm().to_string(),
+                frequency_peak
+                    .get_seconds(signature.sample_rate_hz)
+                    .to_string(),
+            ])?;
+        }
+    }
+
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Render the frequency peaks of a signature as a PNG image, with time
+/// (FFT pass number) on the horizontal axis and frequency on the vertical
+/// axis. Each band has its own color, and brighter dots are louder peaks.
+pub fn render_constellation_plot(
+    signature: &DecodedSignature,
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let number_fft_passes = signature
+        .frequency_band_to_sound_peaks
+        .iter()
+        .flatten()
+        .map(|frequency_peak| frequency_peak.fft_pass_number + 1)
+        .max()
+        .unwrap_or(0)
+        .max(signature.number_samples / 128)
+        .max(1);
+
+    let fft_passes_per_pixel = number_fft_passes.div_ceil(MAX_PLOT_WIDTH);
+    let width = number_fft_passes.div_ceil(fft_passes_per_pixel);
+
+    let mut image = RgbImage::new(width, PLOT_HEIGHT);
+
+    let bin_to_y = |frequency_bin: u32| PLOT_HEIGHT - 1 - (frequency_bin / 2).min(PLOT_HEIGHT - 1);
+
+    // Draw the boundaries of the frequency bands
+
+    for frequency_hz in [250, 520, 1450, 3500, 5500] {
+        let frequency_bin = frequency_hz * 2048 / signature.sample_rate_hz.max(1);
+        let y = bin_to_y(frequency_bin);
+
+        for x in 0..width {
+            image.put_pixel(x, y, Rgb([60, 60, 60]));
+        }
+    }
+
+    // Draw the peaks
+
+    let max_magnitude = signature
+        .frequency_band_to_sound_peaks
+        .iter()
+        .flatten()
+        .map(|frequency_peak| frequency_peak.peak_magnitude)
+        .max()
+        .unwrap_or(1)
+        .max(1) as f32;
+
+    for frequency_band in FrequencyBand::ALL {
+        let Rgb([red, green, blue]) = BAND_COLORS[frequency_band as usize];
+
+        for frequency_peak in &signature.frequency_band_to_sound_peaks[frequency_band as usize] {
+            let brightness = 0.3 + 0.7 * frequency_peak.peak_magnitude as f32 / max_magnitude;
+            let color = Rgb([
+                (red as f32 * brightness) as u8,
+                (green as f32 * brightness) as u8,
+                (blue as f32 * brightness) as u8,
+            ]);
+
+            let x = frequency_peak.fft_pass_number / fft_passes_per_pixel;
+            let y = bin_to_y(frequency_peak.corrected_peak_frequency_bin as u32 / 64);
+
+            for dot_x in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                for dot_y in y.saturating_sub(1)..=(y + 1).min(PLOT_HEIGHT - 1) {
+                    image.put_pixel(dot_x, dot_y, color);
+                }
+            }
+        }
+    }
+
+    image.save_with_format(output_path, image::ImageFormat::Png)?;
+
+    Ok(())
+}
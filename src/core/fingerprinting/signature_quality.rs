@@ -0,0 +1,174 @@
+//! This module contains code used to estimate, from the frequency peaks of a
+//! signature, whether it is worth sending to Shazam: silence yields few
+//! peaks and noise yields peaks of uniform power, and sending them only
+//! uses up the request budget.
+
+use serde::Serialize;
+
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+
+/// Default minimal quality score for a signature to be sent, in percent.
+/// This is enough for skipping silence, white noise and very faint sound.
+pub const DEFAULT_MIN_QUALITY_PERCENT: u64 = 10;
+
+/// Number of frequency peaks per second above which a signature is
+/// considered dense enough (music typically yields 60 to 100).
+const GOOD_PEAKS_PER_SECOND: f32 = 40.0;
+
+/// Levels below the first one are considered as silent, and levels above
+/// the second one as loud enough, in decibels.
+const SILENT_RMS_LEVEL_DB: f32 = 10.0;
+const GOOD_RMS_LEVEL_DB: f32 = 40.0;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SignatureQuality {
+    /// Number of frequency peaks per second in each frequency band, from
+    /// the lowest to the highest one.
+    pub peaks_per_second_per_band: [f32; 4],
+    /// Ratio between the geometric and the arithmetic means of the power
+    /// of the frequency peaks kept in the signature (rather than of the
+    /// whole spectrum), from 0 (very contrasted) to 1 (uniform, as with
+    /// noise).
+    pub peak_power_flatness: f32,
+    /// Root mean square level of the frequency peaks, in decibels.
+    pub rms_level_db: f32,
+    /// Overall quality, between 0 and 1, combining the density of the
+    /// peaks, their contrast and their level.
+    pub score: f32,
+}
+
+impl DecodedSignature {
+    pub fn quality(&self) -> SignatureQuality {
+        let duration_secs = self.number_samples as f32 / self.sample_rate_hz as f32;
+
+        let mut peaks_per_second_per_band = [0.0f32; 4];
+
+        for (frequency_band, frequency_peaks) in
+            self.frequency_band_to_sound_peaks.iter().enumerate()
+        {
+            if duration_secs > 0.0 {
+                peaks_per_second_per_band[frequency_band] =
+                    frequency_peaks.len() as f32 / duration_secs;
+            }
+        }
+
+        // Peak magnitudes are stored as "ln(power) * 1477.3 + 6144", convert
+        // them back to linear powers
+
+        let powers: Vec<f64> = self
+            .frequency_band_to_sound_peaks
+            .iter()
+            .flatten()
+            .map(|frequency_peak| ((frequency_peak.peak_magnitude as f64 - 6144.0) / 1477.3).exp())
+            .collect();
+
+        let (peak_power_flatness, rms_level_db) = if powers.is_empty() {
+            (0.0, f32::NEG_INFINITY)
+        } else {
+            let arithmetic_mean = powers.iter().sum::<f64>() / powers.len() as f64;
+            let geometric_mean =
+                (powers.iter().map(|power| power.ln()).sum::<f64>() / powers.len() as f64).exp();
+
+            (
+                (geometric_mean / arithmetic_mean) as f32,
+                (10.0 * arithmetic_mean.log10()) as f32,
+            )
+        };
+
+        let peaks_per_second: f32 = peaks_per_second_per_band.iter().sum();
+
+        let density_factor = (peaks_per_second / GOOD_PEAKS_PER_SECOND).min(1.0);
+        let contrast_factor = 1.0 - peak_power_flatness;
+        let level_factor = ((rms_level_db - SILENT_RMS_LEVEL_DB)
+            / (GOOD_RMS_LEVEL_DB - SILENT_RMS_LEVEL_DB))
+            .clamp(0.0, 1.0);
+
+        SignatureQuality {
+            peaks_per_second_per_band,
+            peak_power_flatness,
+            rms_level_db,
+            score: density_factor * contrast_factor * level_factor,
+        }
+    }
+}
+
+/// Compute the quality of the signature of 16 KHz mono samples.
+#[cfg(test)]
+fn quality_of_samples(samples: Vec<f32>) -> SignatureQuality {
+    use crate::core::fingerprinting::algorithm::SignatureGenerator;
+
+    // The buffers of the generator need a larger stack than the one of
+    // test threads in debug builds
+
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(move || SignatureGenerator::make_signature_from_buffer(&samples).quality())
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[cfg(test)]
+const MIN_QUALITY: f32 = DEFAULT_MIN_QUALITY_PERCENT as f32 / 100.0;
+
+#[test]
+fn test_signature_quality_silence() {
+    let quality = quality_of_samples(vec![0.0; 16000 * 12]);
+
+    assert_eq!(quality.peaks_per_second_per_band, [0.0; 4]);
+    assert_eq!(quality.score, 0.0);
+}
+
+#[test]
+fn test_signature_quality_white_noise() {
+    // Deterministic pseudo-random samples (xorshift)
+
+    let mut state: u32 = 12345;
+    let samples: Vec<f32> = (0..16000 * 12)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        })
+        .collect();
+
+    let quality = quality_of_samples(samples);
+
+    // Noise yields many peaks, but of uniform power
+    assert!(quality.peaks_per_second_per_band.iter().sum::<f32>() > 100.0);
+    assert!(quality.peak_power_flatness > 0.9);
+    assert!(quality.score < MIN_QUALITY);
+}
+
+#[test]
+fn test_signature_quality_music() {
+    // Notes of a chromatic scale with a few harmonics, each one fading out
+    // over a quarter of a second
+
+    let samples: Vec<f32> = (0..16000 * 12)
+        .map(|index| {
+            let time = index as f32 / 16000.0;
+            let frequency = 220.0 * 2f32.powf(((index / 4000) % 17) as f32 / 12.0);
+            let envelope = (-((index % 4000) as f32) / 2000.0).exp();
+
+            (1..5)
+                .map(|harmonic| {
+                    (time * frequency * harmonic as f32 * std::f32::consts::TAU).sin()
+                        / harmonic as f32
+                })
+                .sum::<f32>()
+                * 0.3
+                * envelope
+        })
+        .collect();
+
+    let quality = quality_of_samples(samples.clone());
+
+    assert!(quality.peak_power_flatness < 0.1);
+    assert!(quality.score > MIN_QUALITY);
+
+    // The same music played very faintly is skipped
+    let faint_quality = quality_of_samples(samples.iter().map(|sample| sample * 0.001).collect());
+    assert!(faint_quality.score < MIN_QUALITY);
+}
@@ -0,0 +1,281 @@
+//! This module contains code used to compare two signatures offline, e.g.
+//! in order to check whether two audio files are the same recording.
+//!
+//! Every pair of peaks from the same frequency band whose frequencies are
+//! close enough votes for the time offset between them. The most voted
+//! offset is used to align the signatures, and the score is the share of
+//! the peaks of the signature with the fewest peaks which then find a
+//! counterpart in the other one. The score is thus the same whatever the
+//! order of the signatures, and an excerpt of a recording scores 1 against
+//! the whole recording.
+
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::core::fingerprinting::signature_format::{DecodedSignature, SignatureError};
+
+/// Default maximal distance between the frequencies of two matching peaks,
+/// in FFT bins (a FFT bin is about 7.8 Hz wide at 16 KHz).
+pub const DEFAULT_FREQUENCY_TOLERANCE: u32 = 1;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SignatureSimilarity {
+    /// Share of the peaks of the signature with the fewest peaks that have
+    /// a counterpart in the other one, between 0 and 1.
+    pub score: f32,
+    /// Number of peaks matching once the signatures are aligned.
+    pub matching_peaks: usize,
+    /// Position of the start of the first signature within the second one.
+    pub offset_fft_passes: i64,
+    pub offset_secs: f32,
+}
+
+impl DecodedSignature {
+    pub fn similarity(
+        &self,
+        other: &DecodedSignature,
+    ) -> Result<SignatureSimilarity, SignatureError> {
+        self.similarity_with_tolerance(other, DEFAULT_FREQUENCY_TOLERANCE)
+    }
+
+    /// Compare two signatures, considering peaks whose frequencies differ
+    /// by at most `frequency_tolerance` FFT bins as matching.
+    pub fn similarity_with_tolerance(
+        &self,
+        other: &DecodedSignature,
+        frequency_tolerance: u32,
+    ) -> Result<SignatureSimilarity, SignatureError> {
+        if self.sample_rate_hz != other.sample_rate_hz {
+            return Err(SignatureError::SampleRateMismatch {
+                expected: self.sample_rate_hz,
+                actual: other.sample_rate_hz,
+            });
+        }
+
+        // Index the peaks of the other signature by band and FFT bin
+
+        let mut other_peaks: HashMap<(usize, u32), Vec<u32>> = HashMap::new();
+
+        for (frequency_band, frequency_peaks) in
+            other.frequency_band_to_sound_peaks.iter().enumerate()
+        {
+            for frequency_peak in frequency_peaks {
+                let frequency_bin = (frequency_peak.corrected_peak_frequency_bin >> 6) as u32;
+
+                other_peaks
+                    .entry((frequency_band, frequency_bin))
+                    .or_default()
+                    .push(frequency_peak.fft_pass_number);
+            }
+        }
+
+        // Find the most voted time offset
+
+        let mut offset_votes: HashMap<i64, usize> = HashMap::new();
+
+        for (frequency_band, frequency_peaks) in
+            self.frequency_band_to_sound_peaks.iter().enumerate()
+        {
+            for frequency_peak in frequency_peaks {
+                let frequency_bin = (frequency_peak.corrected_peak_frequency_bin >> 6) as u32;
+
+                for other_frequency_bin in frequency_bin.saturating_sub(frequency_tolerance)
+                    ..=frequency_bin.saturating_add(frequency_tolerance)
+                {
+                    for other_fft_pass_number in other_peaks
+                        .get(&(frequency_band, other_frequency_bin))
+                        .into_iter()
+                        .flatten()
+                    {
+                        *offset_votes
+                            .entry(
+                                *other_fft_pass_number as i64
+                                    - frequency_peak.fft_pass_number as i64,
+                            )
+                            .or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        // Prefer the smallest offset in case of a tie (and the positive one
+        // between opposite offsets), so that the result does not depend on
+        // the hash map ordering
+
+        let offset = offset_votes
+            .into_iter()
+            .max_by_key(|(offset, votes)| (*votes, std::cmp::Reverse(offset.abs()), *offset))
+            .map(|(offset, _votes)| offset)
+            .unwrap_or(0);
+
+        // Count the peaks of the signature with the fewest peaks having a
+        // counterpart in the other one once aligned (both ways when they
+        // have as many peaks), so that the score doesn't depend on the
+        // order of the signatures
+
+        let number_peaks = |signature: &DecodedSignature| -> usize {
+            signature
+                .frequency_band_to_sound_peaks
+                .iter()
+                .map(Vec::len)
+                .sum()
+        };
+
+        let (number_self_peaks, number_other_peaks) = (number_peaks(self), number_peaks(other));
+        let min_number_peaks = number_self_peaks.min(number_other_peaks);
+
+        let matching_peaks = match number_self_peaks.cmp(&number_other_peaks) {
+            Ordering::Less => count_matching_peaks(self, other, offset, frequency_tolerance),
+            Ordering::Greater => count_matching_peaks(other, self, -offset, frequency_tolerance),
+            Ordering::Equal => {
+                (count_matching_peaks(self, other, offset, frequency_tolerance)
+                    + count_matching_peaks(other, self, -offset, frequency_tolerance))
+                    / 2
+            }
+        };
+
+        Ok(SignatureSimilarity {
+            score: if min_number_peaks > 0 {
+                matching_peaks as f32 / min_number_peaks as f32
+            } else {
+                0.0
+            },
+            matching_peaks,
+            offset_fft_passes: offset,
+            offset_secs: offset as f32 * 128.0 / self.sample_rate_hz as f32,
+        })
+    }
+}
+
+/// Count the peaks of `signature` which have a counterpart in `reference`
+/// once moved by `offset` FFT passes. Each peak is counted at most once.
+fn count_matching_peaks(
+    signature: &DecodedSignature,
+    reference: &DecodedSignature,
+    offset: i64,
+    frequency_tolerance: u32,
+) -> usize {
+    let reference_peak_positions: HashSet<(usize, u32, u32)> = reference
+        .frequency_band_to_sound_peaks
+        .iter()
+        .enumerate()
+        .flat_map(|(frequency_band, frequency_peaks)| {
+            frequency_peaks.iter().map(move |frequency_peak| {
+                (
+                    frequency_band,
+                    (frequency_peak.corrected_peak_frequency_bin >> 6) as u32,
+                    frequency_peak.fft_pass_number,
+                )
+            })
+        })
+        .collect();
+
+    let mut matching_peaks: usize = 0;
+
+    for (frequency_band, frequency_peaks) in
+        signature.frequency_band_to_sound_peaks.iter().enumerate()
+    {
+        for frequency_peak in frequency_peaks {
+            let frequency_bin = (frequency_peak.corrected_peak_frequency_bin >> 6) as u32;
+            let reference_fft_pass_number = frequency_peak.fft_pass_number as i64 + offset;
+
+            if reference_fft_pass_number < 0 || reference_fft_pass_number > u32::MAX as i64 {
+                continue;
+            }
+
+            if (frequency_bin.saturating_sub(frequency_tolerance)
+                ..=frequency_bin.saturating_add(frequency_tolerance))
+                .any(|reference_frequency_bin| {
+                    reference_peak_positions.contains(&(
+                        frequency_band,
+                        reference_frequency_bin,
+                        reference_fft_pass_number as u32,
+                    ))
+                })
+            {
+                matching_peaks += 1;
+            }
+        }
+    }
+
+    matching_peaks
+}
+
+#[cfg(test)]
+fn make_test_signature(peaks: &[(u32, u16)]) -> DecodedSignature {
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+
+    let mut signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 16000 * 12,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    for &(fft_pass_number, frequency_bin) in peaks {
+        signature.frequency_band_to_sound_peaks[2].push(FrequencyPeak {
+            fft_pass_number,
+            peak_magnitude: 10000,
+            corrected_peak_frequency_bin: frequency_bin * 64,
+        });
+    }
+
+    signature
+}
+
+#[cfg(test)]
+fn make_test_peaks(number_peaks: u32, seed: u32) -> Vec<(u32, u16)> {
+    let mut seed = seed;
+
+    (0..number_peaks)
+        .map(|index| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (index * 7, (200 + (seed >> 16) % 200) as u16)
+        })
+        .collect()
+}
+
+#[test]
+fn test_signature_similarity_identical() {
+    let signature = make_test_signature(&make_test_peaks(200, 1));
+
+    let similarity = signature.similarity(&signature).unwrap();
+    assert_eq!(similarity.score, 1.0);
+    assert_eq!(similarity.matching_peaks, 200);
+    assert_eq!(similarity.offset_fft_passes, 0);
+}
+
+#[test]
+fn test_signature_similarity_disjoint() {
+    // The same positions, at frequencies far from each other
+
+    let first = make_test_signature(&make_test_peaks(200, 1));
+    let second = make_test_signature(
+        &make_test_peaks(200, 1)
+            .into_iter()
+            .map(|(fft_pass_number, frequency_bin)| (fft_pass_number, frequency_bin + 400))
+            .collect::<Vec<_>>(),
+    );
+
+    let similarity = first.similarity(&second).unwrap();
+    assert_eq!(similarity.score, 0.0);
+    assert_eq!(similarity.matching_peaks, 0);
+}
+
+#[test]
+fn test_signature_similarity_time_shifted() {
+    // An excerpt starting 100 FFT passes within a longer recording
+
+    let recording = make_test_signature(&make_test_peaks(300, 2));
+    let excerpt = recording.time_shift(-100).slice(0, 1000);
+
+    let similarity = excerpt.similarity(&recording).unwrap();
+    assert_eq!(similarity.score, 1.0);
+    assert_eq!(similarity.offset_fft_passes, 100);
+
+    // The score is the same either way, and the offset opposite
+
+    let reverse_similarity = recording.similarity(&excerpt).unwrap();
+    assert_eq!(reverse_similarity.score, similarity.score);
+    assert_eq!(reverse_similarity.offset_fft_passes, -100);
+}
@@ -1,127 +1,282 @@
-use gettextrs::gettext;
-use serde_json::Value;
+use crate::utils::internationalization::gettext;
 use soup::prelude::SessionExt;
 use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::core::thread_messages::*;
 
 use crate::core::fingerprinting::communication::{
-    obtain_raw_cover_image, recognize_song_from_signature,
+    RetryableStatusError, obtain_raw_cover_image_with_endpoint,
 };
 use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::preferences::PreferencesInterface;
+use crate::core::recognition_backends::recognition_backend::RecognitionBackend;
 
-async fn try_recognize_song(
-    session: &soup::Session,
-    signature: DecodedSignature,
-) -> Result<SongRecognizedMessage, Box<dyn Error>> {
-    let json_object = recognize_song_from_signature(session, &signature).await?;
-
-    let mut album_name: Option<String> = None;
-    let mut release_year: Option<String> = None;
-
-    // Sometimes the idea of trying to write functional poetry hurts
-
-    if let Value::Array(sections) = &json_object["track"]["sections"] {
-        for section in sections {
-            if let Value::String(string) = &section["type"]
-                && string == "SONG"
-            {
-                if let Value::Array(metadata) = &section["metadata"] {
-                    for metadatum in metadata {
-                        if let Value::String(title) = &metadatum["title"] {
-                            if title == "Album"
-                                && let Value::String(text) = &metadatum["text"]
-                            {
-                                album_name = Some(text.to_string());
-                            } else if title == "Released"
-                                && let Value::String(text) = &metadatum["text"]
-                            {
-                                release_year = Some(text.to_string());
-                            }
-                        }
-                    }
-                    break;
-                }
+/// Number of times a request is retried after failing to reach the server,
+/// or after a 429 or 5xx response.
+const MAX_NETWORK_RETRIES: u32 = 2;
+const MAX_STATUS_RETRIES: u32 = 2;
+
+/// Delays before retrying a request for the first time, doubled after each
+/// consecutive failure.
+const NETWORK_RETRY_DELAY: Duration = Duration::from_secs(2);
+const STATUS_RETRY_DELAY: Duration = Duration::from_secs(15);
+
+/// Backoff delays never exceed this, though delays requested by the server
+/// through Retry-After are honored up to an hour.
+const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(600);
+
+/// Decides when requests may be sent, after the server asked us to slow
+/// down (429), failed (5xx) or could not be reached. No request is sent
+/// until the cooldown that followed the last failure has elapsed, and the
+/// cooldown grows exponentially with consecutive 429 and 5xx responses.
+#[derive(Default)]
+pub(crate) struct RetryScheduler {
+    cooldown_until: Option<Instant>,
+    consecutive_status_failures: u32,
+}
+
+/// Double `base_delay` for each previous failure, and randomize the result
+/// between half of it and all of it, so that clients failing together do
+/// not retry together.
+fn backoff_delay(base_delay: Duration, previous_failures: u32) -> Duration {
+    let delay = base_delay
+        .saturating_mul(2_u32.saturating_pow(previous_failures))
+        .min(MAX_BACKOFF_DELAY);
+
+    delay.mul_f64(rand::random_range(0.5..=1.0))
+}
+
+impl RetryScheduler {
+    /// Wait until the cooldown has elapsed, reporting the remaining time
+    /// every second.
+    pub(crate) async fn wait_for_cooldown(&mut self, report_remaining: impl Fn(Duration)) {
+        let Some(cooldown_until) = self.cooldown_until.take() else {
+            return;
+        };
+
+        loop {
+            let remaining = cooldown_until.saturating_duration_since(Instant::now());
+            report_remaining(remaining);
+
+            if remaining.is_zero() {
+                break;
             }
+            glib::timeout_future(remaining.min(Duration::from_secs(1))).await;
         }
     }
 
-    Ok(SongRecognizedMessage {
-        artist_name: match &json_object["track"]["subtitle"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
-            }
-        },
-        album_name,
-        song_name: match &json_object["track"]["title"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
-            }
-        },
-        cover_image: match &json_object["track"]["images"]["coverart"] {
-            Value::String(string) => Some(obtain_raw_cover_image(session, string).await?),
-            _ => None,
-        },
-        track_key: match &json_object["track"]["key"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
+    /// Tell whether a request which failed with the given error, after
+    /// being retried `retries` times, should be retried, and start the
+    /// cooldown to wait for before any other request.
+    pub(crate) fn schedule_retry(&mut self, error: &(dyn Error + 'static), retries: u32) -> bool {
+        let (delay, max_retries) =
+            if let Some(status_error) = error.downcast_ref::<RetryableStatusError>() {
+                self.consecutive_status_failures += 1;
+
+                let delay = status_error.retry_after.unwrap_or_else(|| {
+                    backoff_delay(STATUS_RETRY_DELAY, self.consecutive_status_failures - 1)
+                });
+                (delay, MAX_STATUS_RETRIES)
+            } else if error.is::<glib::Error>() {
+                // The network or the server could not be reached
+                (
+                    backoff_delay(NETWORK_RETRY_DELAY, retries),
+                    MAX_NETWORK_RETRIES,
+                )
+            } else {
+                self.consecutive_status_failures = 0;
+                return false;
+            };
+
+        let now = Instant::now();
+        self.cooldown_until = Some(now.checked_add(delay).unwrap_or(now + MAX_BACKOFF_DELAY));
+
+        retries < max_retries
+    }
+
+    pub(crate) fn request_succeeded(&mut self) {
+        self.consecutive_status_failures = 0;
+    }
+}
+
+async fn try_recognize_song(
+    session: &soup::Session,
+    backend: &dyn RecognitionBackend,
+    cover_endpoint: Option<&str>,
+    signature: &DecodedSignature,
+) -> Result<Option<SongRecognizedMessage>, Box<dyn Error>> {
+    let Some(result) = backend.recognize(signature).await? else {
+        return Ok(None);
+    };
+
+    // Not obtaining the cover should not prevent from showing the song,
+    // e.g. when recognizing from recorded responses without network access
+
+    let cover_image = match &result.cover_image_url {
+        Some(url) => match obtain_raw_cover_image_with_endpoint(session, cover_endpoint, url).await
+        {
+            Ok(cover_image) => Some(cover_image),
+            Err(error) => {
+                log::warn!("Could not obtain the cover image: {:?}", error);
+                None
             }
         },
-        release_year,
-        genre: match &json_object["track"]["genres"]["primary"] {
-            Value::String(string) => Some(string.to_string()),
-            _ => None,
-        },
-        shazam_json: serde_json::to_string(&json_object).unwrap(),
-    })
+        None => None,
+    };
+
+    Ok(Some(SongRecognizedMessage {
+        artist_name: result.artist_name,
+        album_name: result.album_name,
+        song_name: result.song_name,
+        cover_image,
+        track_key: result.track_key,
+        release_year: result.release_year,
+        genre: result.genre,
+        lyrics: result.lyrics,
+        isrc: result.isrc,
+        streaming_links: result.streaming_links,
+        track_position: result.track_position,
+        match_details: result.match_details,
+        shazam_json: serde_json::to_string(&result.raw_response).unwrap(),
+    }))
 }
 
 pub async fn http_task(
     http_rx: async_channel::Receiver<HTTPMessage>,
     gui_tx: async_channel::Sender<GUIMessage>,
     microphone_tx: async_channel::Sender<MicrophoneMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+    backend: Box<dyn RecognitionBackend>,
+    cover_endpoint: Option<String>,
 ) {
     let session = soup::Session::new();
     session.set_timeout(20);
     session.set_idle_timeout(2);
 
+    let mut retry_scheduler = RetryScheduler::default();
+
     while let Ok(message) = http_rx.recv().await {
         // XX USE SOUP3 CF. https://github.com/marin-m/SongRec/issues/223
         match message {
-            HTTPMessage::RecognizeSignature(signature) => {
-                match try_recognize_song(&session, *signature).await {
-                    Ok(recognized_song) => {
+            HTTPMessage::RecognizeSignatures(signatures, origin) => {
+                let mut result: Result<Option<SongRecognizedMessage>, Box<dyn Error>> = Ok(None);
+
+                for (index, signature) in signatures.into_iter().enumerate() {
+                    // Space out the requests for the excerpts of the same
+                    // input like the ones for the microphone, in order to
+                    // avoid being rate-limited
+
+                    if index > 0 {
+                        let request_interval_secs = preferences_interface
+                            .lock()
+                            .unwrap()
+                            .preferences
+                            .request_interval_secs_v3
+                            .unwrap();
+                        glib::timeout_future(Duration::from_secs(request_interval_secs)).await;
+                    }
+
+                    let mut retries = 0;
+
+                    result = loop {
+                        retry_scheduler
+                            .wait_for_cooldown(|remaining| {
+                                gui_tx
+                                    .try_send(GUIMessage::RetryCooldown(remaining))
+                                    .unwrap();
+                            })
+                            .await;
+
+                        let result = try_recognize_song(
+                            &session,
+                            backend.as_ref(),
+                            cover_endpoint.as_deref(),
+                            &signature,
+                        )
+                        .await;
+
+                        match &result {
+                            Err(error) => {
+                                if let Some(status_error) =
+                                    error.downcast_ref::<RetryableStatusError>()
+                                    && status_error.is_rate_limited()
+                                {
+                                    gui_tx.try_send(GUIMessage::RateLimitState(true)).unwrap();
+                                }
+
+                                if retry_scheduler.schedule_retry(error.as_ref(), retries) {
+                                    log::warn!("Retrying a failed request: {}", error);
+                                    retries += 1;
+                                    continue;
+                                }
+                            }
+                            Ok(_) => retry_scheduler.request_succeeded(),
+                        }
+                        break result;
+                    };
+
+                    // Only try the next excerpt if this one was not
+                    // recognized
+
+                    if !matches!(result, Ok(None)) {
+                        break;
+                    }
+                }
+
+                // Let the microphone thread pause its requests while the
+                // recognized song is playing, but not for recognized files
+
+                if origin == SignatureOrigin::Microphone {
+                    let track_position = match &result {
+                        Ok(Some(recognized_song)) => recognized_song.track_position,
+                        _ => None,
+                    };
+
+                    microphone_tx
+                        .try_send(match track_position {
+                            Some(track_position) => MicrophoneMessage::SongPlaying(track_position),
+                            None => MicrophoneMessage::SongPositionUnknown,
+                        })
+                        .unwrap();
+                }
+
+                match result {
+                    Ok(Some(recognized_song)) => {
                         gui_tx
                             .try_send(GUIMessage::SongRecognized(Box::new(recognized_song)))
                             .unwrap();
                         gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
                         gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
                     }
-                    Err(error) => match error.to_string().as_str() {
-                        a if a == gettext("No match for this song") => {
+                    Ok(None) => {
+                        gui_tx
+                            .try_send(GUIMessage::ErrorMessage(gettext("No match for this song")))
+                            .unwrap();
+                        gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
+                        gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
+                    }
+                    Err(error) => {
+                        if let Some(status_error) = error.downcast_ref::<RetryableStatusError>() {
+                            if status_error.is_rate_limited() {
+                                gui_tx.try_send(GUIMessage::RateLimitState(true)).unwrap();
+                            } else {
+                                // The server answered, though with an error
+                                gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
+                                gui_tx
+                                    .try_send(GUIMessage::ErrorMessage(status_error.to_string()))
+                                    .unwrap();
+                            }
+                        } else if error.is::<glib::Error>() {
+                            log::error!("Network reach error: {:?}", error);
+                            gui_tx.try_send(GUIMessage::NetworkStatus(false)).unwrap();
+                        } else {
                             gui_tx
                                 .try_send(GUIMessage::ErrorMessage(error.to_string()))
                                 .unwrap();
-                            gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
-                            gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
-                        }
-                        a if a == gettext("Your IP has been rate-limited") => {
-                            gui_tx.try_send(GUIMessage::RateLimitState(true)).unwrap();
                         }
-                        _ => {
-                            log::error!("Network reach error: {:?}", error);
-                            gui_tx.try_send(GUIMessage::NetworkStatus(false)).unwrap();
-                        }
-                    },
+                    }
                 };
 
                 microphone_tx
@@ -1,16 +1,23 @@
+use std::error::Error;
+use std::io::Read;
 use std::iter::Copied;
 use std::num::NonZero;
 use std::slice::Iter;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_quality::DEFAULT_MIN_QUALITY_PERCENT;
+use crate::core::music_activity::{MusicActivityDetector, RECORDING_SECS};
 use crate::core::preferences::PreferencesInterface;
+use crate::core::recognition_scheduler::RecognitionScheduler;
 use crate::core::thread_messages::{MicrophoneMessage::*, *};
 
+use crate::utils::internationalization::gettext;
 use cpal::platform::Device;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use gettextrs::gettext;
-use log::{debug, warn};
+use log::{debug, info, warn};
 use rodio::conversions::SampleTypeConverter;
 use rodio::nz;
 
@@ -25,9 +32,12 @@ struct ProcessingState<'a> {
     channels: u16,
     sample_rate: u32,
     twelve_seconds_buffer: &'a mut [f32; 16000 * BUFFER_SIZE_SECS],
+    signature_generator: &'a mut SignatureGenerator,
+    music_activity_detector: &'a mut MusicActivityDetector,
     number_unprocessed_samples: &'a mut usize,
     number_unmeasured_samples: &'a mut usize,
     processing_already_ongoing: &'a AtomicBool,
+    recognition_scheduler: &'a Mutex<RecognitionScheduler>,
     preferences_interface: &'a Arc<Mutex<PreferencesInterface>>,
 }
 
@@ -123,6 +133,12 @@ pub fn microphone_thread(
 
         let processing_already_ongoing: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
 
+        // When to recognize the song again, shared with the CPAL thread as
+        // well
+
+        let recognition_scheduler: Arc<Mutex<RecognitionScheduler>> =
+            Arc::new(Mutex::new(RecognitionScheduler::new()));
+
         // Send a list of the active microphone-alike devices to the GUI thread
         // (the combo box will be filed with device names when a "DevicesList"
         // inter-thread message will be received at the initialization of the
@@ -185,10 +201,13 @@ pub fn microphone_thread(
                     let sample_rate = config.sample_rate();
 
                     let mut twelve_seconds_buffer = Box::new([0.0f32; 16000 * BUFFER_SIZE_SECS]);
+                    let mut signature_generator = SignatureGenerator::new();
+                    let mut music_activity_detector = MusicActivityDetector::new();
                     let mut number_unprocessed_samples: usize = 0; // Sample count for the interval of doing Shazam recognition (every 4 seconds)
                     let mut number_unmeasured_samples: usize = 0; // Sample count for doing volume measurement (every 24th of second)
 
                     let processing_already_ongoing_2 = processing_already_ongoing.clone();
+                    let recognition_scheduler_2 = recognition_scheduler.clone();
 
                     let preferences_interface = preferences_interface.clone();
                     macro_rules! build_input_streams {
@@ -208,9 +227,12 @@ pub fn microphone_thread(
                                             channels,
                                             sample_rate,
                                             twelve_seconds_buffer: &mut twelve_seconds_buffer,
+                                            signature_generator: &mut signature_generator,
+                                            music_activity_detector: &mut music_activity_detector,
                                             number_unprocessed_samples: &mut number_unprocessed_samples,
                                             number_unmeasured_samples: &mut number_unmeasured_samples,
                                             processing_already_ongoing: &processing_already_ongoing_2,
+                                            recognition_scheduler: &recognition_scheduler_2,
                                             preferences_interface: &preferences_interface,
                                         })
                                     },
@@ -254,9 +276,12 @@ pub fn microphone_thread(
                                                 channels,
                                                 sample_rate,
                                                 twelve_seconds_buffer: &mut twelve_seconds_buffer,
+                                                signature_generator: &mut signature_generator,
+                                                music_activity_detector: &mut music_activity_detector,
                                                 number_unprocessed_samples: &mut number_unprocessed_samples,
                                                 number_unmeasured_samples: &mut number_unmeasured_samples,
                                                 processing_already_ongoing: &processing_already_ongoing_2,
+                                                recognition_scheduler: &recognition_scheduler_2,
                                                 preferences_interface: &preferences_interface,
                                             })
                                         },
@@ -336,6 +361,20 @@ pub fn microphone_thread(
                     stream = None;
                 }
 
+                SongPlaying(track_position) => {
+                    recognition_scheduler
+                        .lock()
+                        .unwrap()
+                        .song_recognized(track_position);
+                }
+
+                SongPositionUnknown => {
+                    recognition_scheduler
+                        .lock()
+                        .unwrap()
+                        .song_position_unknown();
+                }
+
                 ProcessingDone => {
                     processing_already_ongoing.store(false, Ordering::SeqCst);
                 }
@@ -345,9 +384,203 @@ pub fn microphone_thread(
     }
 }
 
+/// Sample formats accepted for raw PCM input, named as in FFMpeg's "-f"
+/// option.
+#[derive(Clone, Copy, Debug)]
+pub enum RawSampleFormat {
+    U8,
+    S16LE,
+    S16BE,
+    S24LE,
+    S32LE,
+    F32LE,
+}
+
+impl RawSampleFormat {
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            RawSampleFormat::U8 => 1,
+            RawSampleFormat::S16LE | RawSampleFormat::S16BE => 2,
+            RawSampleFormat::S24LE => 3,
+            RawSampleFormat::S32LE | RawSampleFormat::F32LE => 4,
+        }
+    }
+
+    fn decode_sample(&self, bytes: &[u8]) -> f32 {
+        match self {
+            RawSampleFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            RawSampleFormat::S16LE => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            RawSampleFormat::S16BE => i16::from_be_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            RawSampleFormat::S24LE => {
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.0
+            }
+            RawSampleFormat::S32LE => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0
+            }
+            RawSampleFormat::F32LE => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// Description of a raw PCM stream read from the standard input, given
+/// on the command line as "<rate>:<channels>:<format>" (e.g.
+/// "44100:2:s16le").
+#[derive(Clone, Copy, Debug)]
+pub struct RawPcmFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: RawSampleFormat,
+}
+
+impl FromStr for RawPcmFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let invalid_format = || -> Box<dyn Error> {
+            Box::new(std::io::Error::other(gettext(
+                "Invalid raw PCM format, expected <rate>:<channels>:<format> (e.g. 44100:2:s16le)",
+            )))
+        };
+
+        let [sample_rate, channels, sample_format] = string
+            .split(':')
+            .collect::<Vec<&str>>()
+            .try_into()
+            .map_err(|_| invalid_format())?;
+
+        let sample_rate: u32 = sample_rate.parse().map_err(|_| invalid_format())?;
+        let channels: u16 = channels.parse().map_err(|_| invalid_format())?;
+
+        if sample_rate == 0 || channels == 0 {
+            return Err(invalid_format());
+        }
+
+        Ok(RawPcmFormat {
+            sample_rate,
+            channels,
+            sample_format: match sample_format {
+                "u8" => RawSampleFormat::U8,
+                "s16le" | "s16" => RawSampleFormat::S16LE,
+                "s16be" => RawSampleFormat::S16BE,
+                "s24le" | "s24" => RawSampleFormat::S24LE,
+                "s32le" | "s32" => RawSampleFormat::S32LE,
+                "f32le" | "f32" => RawSampleFormat::F32LE,
+                _ => {
+                    return Err(invalid_format());
+                }
+            },
+        })
+    }
+}
+
+/// This function replaces `microphone_thread` when reading a continuous
+/// raw PCM stream from the standard input (e.g. piped from "parec",
+/// "pw-record", "sox" or "ffmpeg") rather than capturing audio through
+/// CPAL.
+pub fn raw_pcm_thread(
+    microphone_rx: async_channel::Receiver<MicrophoneMessage>,
+    processing_tx: async_channel::Sender<ProcessingMessage>,
+    gui_tx: async_channel::Sender<GUIMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+    raw_pcm_format: RawPcmFormat,
+) {
+    let processing_already_ongoing: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let processing_already_ongoing_2 = processing_already_ongoing.clone();
+
+    let recognition_scheduler: Arc<Mutex<RecognitionScheduler>> =
+        Arc::new(Mutex::new(RecognitionScheduler::new()));
+    let recognition_scheduler_2 = recognition_scheduler.clone();
+
+    let gui_tx_2 = gui_tx.clone();
+
+    spawn_big_thread(move || {
+        let mut twelve_seconds_buffer = Box::new([0.0f32; 16000 * BUFFER_SIZE_SECS]);
+        let mut signature_generator = SignatureGenerator::new();
+        let mut music_activity_detector = MusicActivityDetector::new();
+        let mut number_unprocessed_samples: usize = 0;
+        let mut number_unmeasured_samples: usize = 0;
+
+        // Read the input by chunks of a 10th of second
+
+        let frame_size =
+            raw_pcm_format.sample_format.bytes_per_sample() * raw_pcm_format.channels as usize;
+        let mut chunk = vec![0u8; frame_size * (raw_pcm_format.sample_rate as usize / 10).max(1)];
+
+        let mut stdin = std::io::stdin().lock();
+
+        loop {
+            if let Err(error) = stdin.read_exact(&mut chunk) {
+                if error.kind() != std::io::ErrorKind::UnexpectedEof {
+                    gui_tx_2
+                        .try_send(GUIMessage::ErrorMessage(format!(
+                            "{} {}",
+                            gettext("Could not read the raw PCM input:"),
+                            error
+                        )))
+                        .unwrap();
+                }
+                break;
+            }
+
+            write_data(ProcessingState {
+                input_samples: chunk
+                    .chunks_exact(raw_pcm_format.sample_format.bytes_per_sample())
+                    .map(|bytes| raw_pcm_format.sample_format.decode_sample(bytes))
+                    .collect(),
+                processing_tx: &processing_tx,
+                gui_tx: gui_tx_2.clone(),
+                channels: raw_pcm_format.channels,
+                sample_rate: raw_pcm_format.sample_rate,
+                twelve_seconds_buffer: &mut twelve_seconds_buffer,
+                signature_generator: &mut signature_generator,
+                music_activity_detector: &mut music_activity_detector,
+                number_unprocessed_samples: &mut number_unprocessed_samples,
+                number_unmeasured_samples: &mut number_unmeasured_samples,
+                processing_already_ongoing: &processing_already_ongoing_2,
+                recognition_scheduler: &recognition_scheduler_2,
+                preferences_interface: &preferences_interface,
+            });
+        }
+
+        info!("{}", gettext("End of the raw PCM input"));
+
+        // Let the last recognition request complete before quitting
+
+        while processing_already_ongoing_2.load(Ordering::SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        gui_tx_2.try_send(GUIMessage::QuitApplication).unwrap();
+    });
+
+    gui_tx.try_send(GUIMessage::MicrophoneRecording).unwrap();
+
+    while let Ok(message) = microphone_rx.recv_blocking() {
+        match message {
+            SongPlaying(track_position) => {
+                recognition_scheduler
+                    .lock()
+                    .unwrap()
+                    .song_recognized(track_position);
+            }
+            SongPositionUnknown => {
+                recognition_scheduler
+                    .lock()
+                    .unwrap()
+                    .song_position_unknown();
+            }
+            ProcessingDone => {
+                processing_already_ongoing.store(false, Ordering::SeqCst);
+            }
+            _ => {}
+        }
+    }
+}
+
 fn write_data(state: ProcessingState) {
-    // Reassemble data into a 12-second buffer, and do recognition
-    // every 4 seconds if the queue to "processing_tx" is empty
+    // Reassemble data into a 12-second buffer, feed the signature generator,
+    // and do recognition every few seconds (or when the song changes) if no
+    // request is ongoing
 
     let input_buffer = rodio::buffer::SamplesBuffer::new(
         NonZero::new(state.channels).unwrap(),
@@ -361,9 +594,15 @@ fn write_data(state: ProcessingState) {
     let raw_pcm_samples: Vec<f32> = converted_file.collect();
 
     let request_interval_secs;
+    let min_quality_percent;
+    let detect_song_changes;
     {
         let preferences = &state.preferences_interface.lock().unwrap().preferences;
         request_interval_secs = preferences.request_interval_secs_v3.unwrap() as usize;
+        detect_song_changes = preferences.detect_song_changes.unwrap_or(false);
+        min_quality_percent = preferences
+            .min_quality_percent
+            .unwrap_or(DEFAULT_MIN_QUALITY_PERCENT);
     }
 
     let twelve_seconds_buffer = &mut state.twelve_seconds_buffer[..16000 * BUFFER_SIZE_SECS];
@@ -374,32 +613,91 @@ fn write_data(state: ProcessingState) {
         twelve_seconds_buffer
             .copy_from_slice(&raw_pcm_samples[raw_pcm_samples.len() - 16000 * BUFFER_SIZE_SECS..]);
     } else {
-        let latter_data = twelve_seconds_buffer[raw_pcm_samples.len()..].to_vec();
-
-        twelve_seconds_buffer[..16000 * BUFFER_SIZE_SECS - raw_pcm_samples.len()]
-            .copy_from_slice(&latter_data);
+        twelve_seconds_buffer.copy_within(raw_pcm_samples.len().., 0);
         twelve_seconds_buffer[16000 * BUFFER_SIZE_SECS - raw_pcm_samples.len()..]
             .copy_from_slice(&raw_pcm_samples);
     }
 
+    // Compute the frequency peaks of the signature as the data comes, so
+    // that we don't have to recompute the FFTs for the whole buffer when
+    // sending a request. Only the peaks of the last 12 seconds are sent,
+    // and older ones are forgotten from time to time.
+
+    state.signature_generator.feed(&raw_pcm_samples);
+
+    if state.signature_generator.number_samples() as usize >= 2 * 16000 * BUFFER_SIZE_SECS {
+        state
+            .signature_generator
+            .keep_last((16000 * BUFFER_SIZE_SECS) as u32);
+    }
+
+    // When detecting song changes, only record what follows the start of
+    // new content. New content also means that the recognized song is not
+    // playing anymore.
+
+    if state.music_activity_detector.feed(&raw_pcm_samples) {
+        state.recognition_scheduler.lock().unwrap().resume();
+
+        if detect_song_changes {
+            *state.number_unprocessed_samples = 0;
+        }
+    }
+
     *state.number_unprocessed_samples += raw_pcm_samples.len();
 
-    if *state.number_unprocessed_samples >= 16000 * request_interval_secs
-        && !state.processing_already_ongoing.load(Ordering::SeqCst)
-    {
+    let (recognition_due, recording_samples) = if detect_song_changes {
+        (
+            state.music_activity_detector.is_recognition_pending()
+                && *state.number_unprocessed_samples >= 16000 * RECORDING_SECS,
+            (*state.number_unprocessed_samples).min(16000 * BUFFER_SIZE_SECS),
+        )
+    } else {
+        (
+            *state.number_unprocessed_samples >= 16000 * request_interval_secs,
+            16000 * BUFFER_SIZE_SECS,
+        )
+    };
+
+    if recognition_due && !state.processing_already_ongoing.load(Ordering::SeqCst) {
         if !twelve_seconds_buffer.iter().all(|x| *x == 0.0) {
-            state
-                .processing_tx
-                .try_send(ProcessingMessage::ProcessAudioSamples(
-                    twelve_seconds_buffer.to_vec(),
-                ))
-                .unwrap();
+            let signature = state
+                .signature_generator
+                .snapshot_last(recording_samples as u32);
+
+            // Don't use up the request budget for recordings with too
+            // little music in them (e.g. silence, noise or speech)
+
+            let quality = signature.quality();
 
-            state
-                .processing_already_ongoing
-                .store(true, Ordering::SeqCst);
+            let mut recognition_scheduler = state.recognition_scheduler.lock().unwrap();
+
+            if quality.score * 100.0 < min_quality_percent as f32 {
+                // The recognized song, if any, seems to have stopped
+
+                recognition_scheduler.resume();
+
+                state
+                    .gui_tx
+                    .try_send(GUIMessage::SignatureSkipped(quality))
+                    .unwrap();
+            } else if recognition_scheduler.should_recognize(&signature) {
+                recognition_scheduler.recording_sent(&signature);
+
+                state
+                    .processing_tx
+                    .try_send(ProcessingMessage::ProcessSignature(Box::new(signature)))
+                    .unwrap();
+
+                state
+                    .processing_already_ongoing
+                    .store(true, Ordering::SeqCst);
+            } else {
+                debug!("Not sending a recording of the song still playing");
+            }
         }
 
+        state.music_activity_detector.recognition_started();
+
         *state.number_unprocessed_samples = 0;
     }
 
@@ -0,0 +1,367 @@
+//! This module contains code used to decide when to recognize the audio
+//! heard from the microphone, as an alternative to sending requests at a
+//! fixed interval: a request is made shortly after silence turns into
+//! music, or after the heard content changes clearly (e.g. when a new
+//! song starts), and no request is made otherwise.
+
+use realfft::{RealFftPlanner, RealToComplex};
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use std::sync::Arc;
+
+/// Number of seconds of new content to record before recognizing it.
+pub const RECORDING_SECS: usize = 8;
+
+/// Number of 16 KHz samples analyzed at once (64 ms).
+const FRAME_SIZE: usize = 1024;
+const FRAMES_PER_SEC: f32 = 16000.0 / FRAME_SIZE as f32;
+
+/// Number of logarithmically spaced frequency bands, between 100 Hz and
+/// 5 KHz, whose energies describe the timbre of the content.
+const NUMBER_BANDS: usize = 16;
+const LOWEST_BAND_HZ: f32 = 100.0;
+const HIGHEST_BAND_HZ: f32 = 5000.0;
+
+/// Frames below this level (in decibels relative to the full scale) are
+/// considered as silent, and a silence has to last this long for the
+/// following sound to be considered as new content.
+const SILENCE_LEVEL_DB: f32 = -50.0;
+const MIN_SILENCE_SECS: f32 = 1.5;
+
+/// Time constants of the short-term and long-term averages of the band
+/// energies, which are compared in order to detect changes.
+const SHORT_TERM_SECS: f32 = 1.5;
+const LONG_TERM_SECS: f32 = 10.0;
+
+/// Mean difference between the short-term and long-term band energies (in
+/// decibels, once the overall level is set apart) above which the content
+/// is considered as having changed, and for how long it has to stay above.
+const NOVELTY_THRESHOLD_DB: f32 = 3.0;
+const NOVELTY_HOLD_SECS: f32 = 1.0;
+
+/// Rise of the short-term level over the long-term level above which the
+/// content is considered as having changed as well (e.g. when music starts
+/// over background noise), in decibels.
+const LOUDNESS_RISE_THRESHOLD_DB: f32 = 12.0;
+
+/// Spectral changes closer than this to the previous change are ignored, as
+/// the long-term average still describes the previous content.
+const MIN_CHANGE_INTERVAL_SECS: f32 = 20.0;
+
+/// Watches the loudness and the spectrum of 16 KHz mono samples in order to
+/// detect when music starts or changes.
+pub struct MusicActivityDetector {
+    fft_object: Arc<dyn RealToComplex<f32>>,
+    hanning_window: Vec<f32>,
+    frame: Vec<f32>,
+    complex_fft_output: Vec<Complex<f32>>,
+
+    /// Samples passed to `feed()` that do not fill a whole frame yet.
+    pending_samples: Vec<f32>,
+
+    short_term_bands_db: [f32; NUMBER_BANDS],
+    long_term_bands_db: [f32; NUMBER_BANDS],
+    short_term_level_db: f32,
+    long_term_level_db: f32,
+
+    number_silent_frames: usize,
+    in_silence: bool,
+    number_frames_since_change: usize,
+    number_novel_frames: usize,
+
+    recognition_pending: bool,
+}
+
+impl Default for MusicActivityDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicActivityDetector {
+    pub fn new() -> Self {
+        MusicActivityDetector {
+            fft_object: RealFftPlanner::<f32>::new().plan_fft_forward(FRAME_SIZE),
+            hanning_window: (0..FRAME_SIZE)
+                .map(|index| {
+                    0.5 - 0.5
+                        * (2.0 * std::f32::consts::PI * index as f32 / FRAME_SIZE as f32).cos()
+                })
+                .collect(),
+            frame: vec![0.0; FRAME_SIZE],
+            complex_fft_output: vec![Complex::zero(); FRAME_SIZE / 2 + 1],
+
+            pending_samples: Vec::with_capacity(FRAME_SIZE),
+
+            short_term_bands_db: [0.0; NUMBER_BANDS],
+            long_term_bands_db: [0.0; NUMBER_BANDS],
+            short_term_level_db: 0.0,
+            long_term_level_db: 0.0,
+
+            number_silent_frames: 0,
+
+            // Consider that music already playing when starting to listen
+            // is new content
+            in_silence: true,
+            number_frames_since_change: 0,
+            number_novel_frames: 0,
+
+            recognition_pending: false,
+        }
+    }
+
+    /// Analyze new 16 KHz mono samples. Returns true when new content
+    /// starts within these samples, in which case a recognition becomes
+    /// pending.
+    pub fn feed(&mut self, samples: &[f32]) -> bool {
+        let mut content_changed = false;
+
+        for &sample in samples {
+            self.pending_samples.push(sample);
+
+            if self.pending_samples.len() == FRAME_SIZE {
+                content_changed |= self.process_frame();
+                self.pending_samples.clear();
+            }
+        }
+
+        content_changed
+    }
+
+    /// Whether new content has been heard since the last recognition.
+    pub fn is_recognition_pending(&self) -> bool {
+        self.recognition_pending
+    }
+
+    /// To be called when the new content has been sent for recognition.
+    pub fn recognition_started(&mut self) {
+        self.recognition_pending = false;
+    }
+
+    fn process_frame(&mut self) -> bool {
+        let mean_square = self
+            .pending_samples
+            .iter()
+            .map(|sample| sample * sample)
+            .sum::<f32>()
+            / FRAME_SIZE as f32;
+
+        let level_db = 10.0 * mean_square.max(1e-12).log10();
+
+        if level_db < SILENCE_LEVEL_DB {
+            self.number_silent_frames += 1;
+
+            if self.number_silent_frames as f32 >= MIN_SILENCE_SECS * FRAMES_PER_SEC {
+                // There is nothing left to recognize
+                self.in_silence = true;
+                self.recognition_pending = false;
+            }
+
+            return false;
+        }
+
+        self.number_silent_frames = 0;
+
+        let bands_db = self.compute_bands_db();
+
+        if self.in_silence {
+            self.in_silence = false;
+            self.short_term_bands_db = bands_db;
+            self.short_term_level_db = level_db;
+            self.start_new_content();
+            return true;
+        }
+
+        self.number_frames_since_change += 1;
+
+        // The long-term average is a plain average of the frames heard
+        // since the last change until it spans its whole duration
+
+        let short_term_factor = 1.0 / (SHORT_TERM_SECS * FRAMES_PER_SEC);
+        let long_term_factor = (1.0 / (self.number_frames_since_change as f32 + 1.0))
+            .max(1.0 / (LONG_TERM_SECS * FRAMES_PER_SEC));
+
+        for (band, band_db) in bands_db.iter().enumerate() {
+            self.short_term_bands_db[band] +=
+                (band_db - self.short_term_bands_db[band]) * short_term_factor;
+            self.long_term_bands_db[band] +=
+                (band_db - self.long_term_bands_db[band]) * long_term_factor;
+        }
+
+        self.short_term_level_db += (level_db - self.short_term_level_db) * short_term_factor;
+        self.long_term_level_db += (level_db - self.long_term_level_db) * long_term_factor;
+
+        if (self.number_frames_since_change as f32) < MIN_CHANGE_INTERVAL_SECS * FRAMES_PER_SEC {
+            return false;
+        }
+
+        if self.novelty_db() > NOVELTY_THRESHOLD_DB
+            || self.short_term_level_db - self.long_term_level_db > LOUDNESS_RISE_THRESHOLD_DB
+        {
+            self.number_novel_frames += 1;
+        } else {
+            self.number_novel_frames = 0;
+        }
+
+        if self.number_novel_frames as f32 >= NOVELTY_HOLD_SECS * FRAMES_PER_SEC {
+            self.start_new_content();
+            return true;
+        }
+
+        false
+    }
+
+    fn start_new_content(&mut self) {
+        // Measure further changes against the new content only
+
+        self.long_term_bands_db = self.short_term_bands_db;
+        self.long_term_level_db = self.short_term_level_db;
+        self.number_frames_since_change = 0;
+        self.number_novel_frames = 0;
+        self.recognition_pending = true;
+    }
+
+    /// Energies of the current frame within each frequency band, in
+    /// decibels.
+    fn compute_bands_db(&mut self) -> [f32; NUMBER_BANDS] {
+        for (output, (sample, multiplier)) in self
+            .frame
+            .iter_mut()
+            .zip(self.pending_samples.iter().zip(&self.hanning_window))
+        {
+            *output = sample * multiplier;
+        }
+
+        self.fft_object
+            .process(&mut self.frame, &mut self.complex_fft_output)
+            .unwrap();
+
+        let hz_per_bin = 16000.0 / FRAME_SIZE as f32;
+        let band_ratio = (HIGHEST_BAND_HZ / LOWEST_BAND_HZ).powf(1.0 / NUMBER_BANDS as f32);
+
+        let mut bands_db = [0.0f32; NUMBER_BANDS];
+
+        for (band, band_db) in bands_db.iter_mut().enumerate() {
+            let lowest_bin =
+                (LOWEST_BAND_HZ * band_ratio.powi(band as i32) / hz_per_bin).round() as usize;
+            let highest_bin = ((LOWEST_BAND_HZ * band_ratio.powi(band as i32 + 1) / hz_per_bin)
+                .round() as usize)
+                .max(lowest_bin + 1);
+
+            let energy: f32 = self.complex_fft_output[lowest_bin..highest_bin]
+                .iter()
+                .map(|bin| bin.norm_sqr())
+                .sum();
+
+            *band_db = 10.0 * energy.max(1e-12).log10();
+        }
+
+        bands_db
+    }
+
+    /// Mean difference between the short-term and long-term band energies,
+    /// regardless of their overall levels, so that volume changes within a
+    /// song are not taken for a new song.
+    fn novelty_db(&self) -> f32 {
+        let short_term_mean = self.short_term_bands_db.iter().sum::<f32>() / NUMBER_BANDS as f32;
+        let long_term_mean = self.long_term_bands_db.iter().sum::<f32>() / NUMBER_BANDS as f32;
+
+        self.short_term_bands_db
+            .iter()
+            .zip(&self.long_term_bands_db)
+            .map(|(short_term, long_term)| {
+                ((short_term - short_term_mean) - (long_term - long_term_mean)).abs()
+            })
+            .sum::<f32>()
+            / NUMBER_BANDS as f32
+    }
+}
+
+/// Generate 16 KHz samples of a sum of tones.
+#[cfg(test)]
+fn make_test_tones(frequencies: &[f64], secs: f32) -> Vec<f32> {
+    (0..(secs * 16000.0) as usize)
+        .map(|index| {
+            frequencies
+                .iter()
+                .map(|&frequency| {
+                    // Compute the phase in double precision, so that
+                    // rounding errors do not add noise over time
+                    (index as f64 * frequency * std::f64::consts::TAU / 16000.0).sin() as f32 * 0.2
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Feed samples by tenths of a second, like the microphone thread does,
+/// and count how many times new content was detected.
+#[cfg(test)]
+fn count_content_changes(detector: &mut MusicActivityDetector, samples: &[f32]) -> usize {
+    samples
+        .chunks(1600)
+        .filter(|chunk| detector.feed(chunk))
+        .count()
+}
+
+#[test]
+fn test_music_activity_after_silence() {
+    let low_tones = [220.0, 440.0, 660.0];
+    let mut detector = MusicActivityDetector::new();
+
+    let silence = vec![0.0; 16000 * 3];
+    assert_eq!(count_content_changes(&mut detector, &silence), 0);
+    assert!(!detector.is_recognition_pending());
+
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&low_tones, 2.0)),
+        1
+    );
+    assert!(detector.is_recognition_pending());
+    detector.recognition_started();
+    assert!(!detector.is_recognition_pending());
+
+    // Steady content is not new, even once the minimum interval between
+    // changes has elapsed
+
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&low_tones, 30.0)),
+        0
+    );
+
+    assert_eq!(count_content_changes(&mut detector, &silence), 0);
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&low_tones, 1.0)),
+        1
+    );
+}
+
+#[test]
+fn test_music_activity_spectral_change() {
+    let low_tones = [220.0, 440.0, 660.0];
+    let high_tones = [1500.0, 2500.0, 3500.0];
+    let mut detector = MusicActivityDetector::new();
+
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&low_tones, 30.0)),
+        1
+    );
+    detector.recognition_started();
+
+    // A clear change of timbre is new content, once it lasted long enough
+
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&high_tones, 4.0)),
+        1
+    );
+    assert!(detector.is_recognition_pending());
+    detector.recognition_started();
+
+    // Changes within 20 seconds of the previous one are ignored
+
+    assert_eq!(
+        count_content_changes(&mut detector, &make_test_tones(&low_tones, 10.0)),
+        0
+    );
+    assert!(!detector.is_recognition_pending());
+}
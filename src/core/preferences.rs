@@ -1,10 +1,12 @@
-use gettextrs::gettext;
+use crate::utils::internationalization::gettext;
 use log::{debug, error};
 use serde::Deserialize;
 use serde::Serialize;
 use std::error::Error;
 use std::path::PathBuf;
 
+use crate::core::fingerprinting::signature_quality::DEFAULT_MIN_QUALITY_PERCENT;
+use crate::core::recognition_backends::recognition_backend::DEFAULT_RECOGNITION_BACKEND;
 use crate::utils::filesystem_operations::obtain_preferences_file_path;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -19,6 +21,17 @@ pub struct Preferences {
     pub request_interval_secs: Option<u64>,    // Legacy, before increasing default from 4 to 10
     pub request_interval_secs_v2: Option<u64>, // before decreasing from 10 to 8
     pub request_interval_secs_v3: Option<u64>,
+    pub min_quality_percent: Option<u64>,
+    pub detect_song_changes: Option<bool>,
+    pub recognition_backend: Option<String>,
+    pub recognition_fixtures_path: Option<String>,
+    pub recognition_command: Option<String>,
+    pub recognition_endpoint: Option<String>,
+    pub cover_endpoint: Option<String>,
+    pub request_locale: Option<String>,
+    pub request_timezone: Option<String>,
+    pub request_latitude: Option<f64>,
+    pub request_longitude: Option<f64>,
     pub current_device_name: Option<String>,
     pub website_search_url: Option<String>,
     pub website_search_text: Option<String>,
@@ -36,6 +49,17 @@ impl Preferences {
             request_interval_secs: None,
             request_interval_secs_v2: None,
             request_interval_secs_v3: None,
+            min_quality_percent: None,
+            detect_song_changes: None,
+            recognition_backend: None,
+            recognition_fixtures_path: None,
+            recognition_command: None,
+            recognition_endpoint: None,
+            cover_endpoint: None,
+            request_locale: None,
+            request_timezone: None,
+            request_latitude: None,
+            request_longitude: None,
             current_device_name: None,
             website_search_url: None,
             website_search_text: None,
@@ -53,6 +77,17 @@ impl Preferences {
             request_interval_secs: None,
             request_interval_secs_v2: None,
             request_interval_secs_v3: Some(interval),
+            min_quality_percent: Some(DEFAULT_MIN_QUALITY_PERCENT),
+            detect_song_changes: Some(false),
+            recognition_backend: Some(DEFAULT_RECOGNITION_BACKEND.to_string()),
+            recognition_fixtures_path: None,
+            recognition_command: None,
+            recognition_endpoint: None,
+            cover_endpoint: None,
+            request_locale: None,
+            request_timezone: None,
+            request_latitude: None,
+            request_longitude: None,
             current_device_name: None,
             website_search_url: Some("https://www.youtube.com/results?search_query=".to_string()),
             website_search_text: Some(gettext("Search on YouTube".to_string())),
@@ -72,6 +107,17 @@ impl Default for Preferences {
             request_interval_secs: None,
             request_interval_secs_v2: None,
             request_interval_secs_v3: Some(8),
+            min_quality_percent: Some(DEFAULT_MIN_QUALITY_PERCENT),
+            detect_song_changes: Some(false),
+            recognition_backend: Some(DEFAULT_RECOGNITION_BACKEND.to_string()),
+            recognition_fixtures_path: None,
+            recognition_command: None,
+            recognition_endpoint: None,
+            cover_endpoint: None,
+            request_locale: None,
+            request_timezone: None,
+            request_latitude: None,
+            request_longitude: None,
             current_device_name: None,
             website_search_url: Some("https://www.youtube.com/results?search_query=".to_string()),
             website_search_text: Some(gettext("Search on YouTube".to_string())),
@@ -86,6 +132,8 @@ pub struct PreferencesInterface {
 }
 
 impl PreferencesInterface {
+    // Reading the preferences file from the disk is not a sensible default
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         match PreferencesInterface::load() {
             Ok(preferences_interface) => preferences_interface,
@@ -147,6 +195,39 @@ impl PreferencesInterface {
                     None => None,
                 })
                 .or(current_preferences.request_interval_secs_v3),
+            min_quality_percent: update_preferences
+                .min_quality_percent
+                .or(current_preferences.min_quality_percent),
+            detect_song_changes: update_preferences
+                .detect_song_changes
+                .or(current_preferences.detect_song_changes),
+            recognition_backend: update_preferences
+                .recognition_backend
+                .or_else(|| current_preferences.recognition_backend.clone()),
+            recognition_fixtures_path: update_preferences
+                .recognition_fixtures_path
+                .or_else(|| current_preferences.recognition_fixtures_path.clone()),
+            recognition_command: update_preferences
+                .recognition_command
+                .or_else(|| current_preferences.recognition_command.clone()),
+            recognition_endpoint: update_preferences
+                .recognition_endpoint
+                .or_else(|| current_preferences.recognition_endpoint.clone()),
+            cover_endpoint: update_preferences
+                .cover_endpoint
+                .or_else(|| current_preferences.cover_endpoint.clone()),
+            request_locale: update_preferences
+                .request_locale
+                .or_else(|| current_preferences.request_locale.clone()),
+            request_timezone: update_preferences
+                .request_timezone
+                .or_else(|| current_preferences.request_timezone.clone()),
+            request_latitude: update_preferences
+                .request_latitude
+                .or(current_preferences.request_latitude),
+            request_longitude: update_preferences
+                .request_longitude
+                .or(current_preferences.request_longitude),
             current_device_name: update_preferences
                 .current_device_name
                 .or_else(|| current_preferences.current_device_name.clone()),
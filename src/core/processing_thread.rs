@@ -8,19 +8,18 @@ pub fn processing_thread(
     gui_tx: async_channel::Sender<GUIMessage>,
 ) {
     while let Ok(message) = processing_rx.recv_blocking() {
-        let signature = match message {
-            ProcessAudioFile(input_file_string) => {
-                SignatureGenerator::make_signature_from_file(&input_file_string)
-            }
-            ProcessAudioSamples(audio_samples) => Ok(
-                SignatureGenerator::make_signature_from_buffer(&audio_samples),
+        let (signatures, origin) = match message {
+            ProcessAudioFile(input_file_string, window) => (
+                SignatureGenerator::make_signatures_from_file(&input_file_string, &window),
+                SignatureOrigin::File,
             ),
+            ProcessSignature(signature) => (Ok(vec![*signature]), SignatureOrigin::Microphone),
         };
 
-        match signature {
-            Ok(signature) => {
+        match signatures {
+            Ok(signatures) => {
                 http_tx
-                    .try_send(HTTPMessage::RecognizeSignature(Box::new(signature)))
+                    .try_send(HTTPMessage::RecognizeSignatures(signatures, origin))
                     .unwrap();
             }
             Err(error) => {
@@ -0,0 +1,79 @@
+use serde_json::Value;
+use std::error::Error;
+use std::process::Command;
+
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::recognition_backend::{
+    RecognitionBackend, RecognitionFuture,
+};
+use crate::core::recognition_backends::shazam::parse_shazam_response;
+use crate::utils::internationalization::gettext;
+
+/// Hands the signatures to a user command, which receives the signature URI
+/// (see `DecodedSignature::encode_to_uri`) as its last argument and should
+/// print a response in the format of Shazam's API on its standard output,
+/// e.g. "{}" when nothing matched.
+pub struct CommandBackend {
+    program: String,
+    arguments: Vec<String>,
+}
+
+impl CommandBackend {
+    pub fn new(command: &str) -> Result<Self, Box<dyn Error>> {
+        let mut words = command.split_whitespace().map(str::to_string);
+
+        match words.next() {
+            Some(program) => Ok(CommandBackend {
+                program,
+                arguments: words.collect(),
+            }),
+            None => Err(Box::new(std::io::Error::other(gettext(
+                "The command backend requires a command to run",
+            )))),
+        }
+    }
+
+    /// Run the command to completion, returning its standard output.
+    fn run(program: &str, arguments: &[String], uri: &str) -> Result<Vec<u8>, String> {
+        let output = Command::new(program)
+            .args(arguments)
+            .arg(uri)
+            .output()
+            .map_err(|error| error.to_string())?;
+
+        if !output.status.success() {
+            return Err(gettext("The recognition command failed (%s): %s")
+                .replacen("%s", &output.status.to_string(), 1)
+                .replacen("%s", String::from_utf8_lossy(&output.stderr).trim(), 1));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+impl RecognitionBackend for CommandBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move {
+            let uri = signature.encode_to_uri()?;
+
+            // Run the command in its own thread so that the main loop is not
+            // blocked while it is working
+
+            let (result_tx, result_rx) = async_channel::bounded(1);
+            let program = self.program.clone();
+            let arguments = self.arguments.clone();
+
+            std::thread::spawn(move || {
+                result_tx
+                    .send_blocking(Self::run(&program, &arguments, &uri))
+                    .unwrap();
+            });
+
+            let output = result_rx.recv().await?.map_err(std::io::Error::other)?;
+
+            let json_object: Value = serde_json::from_slice(&output)?;
+
+            parse_shazam_response(json_object)
+        })
+    }
+}
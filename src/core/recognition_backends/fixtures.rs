@@ -0,0 +1,75 @@
+use serde_json::Value;
+use std::cell::Cell;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::recognition_backend::{
+    RecognitionBackend, RecognitionFuture,
+};
+use crate::core::recognition_backends::shazam::parse_shazam_response;
+use crate::utils::internationalization::gettext;
+
+/// Answers from responses recorded in Shazam's format, without any network
+/// access, e.g. for testing or demonstrating the application offline.
+///
+/// The path may either be a JSON file, which answers every request, or a
+/// directory whose JSON files answer the successive requests in the order
+/// of their names, starting over after the last one.
+pub struct FixturesBackend {
+    response_paths: Vec<PathBuf>,
+    next_response: Cell<usize>,
+}
+
+impl FixturesBackend {
+    pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
+        let path = Path::new(path);
+
+        let mut response_paths: Vec<PathBuf> = vec![];
+
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry_path = entry?.path();
+                if entry_path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+                {
+                    response_paths.push(entry_path);
+                }
+            }
+            response_paths.sort();
+        } else {
+            response_paths.push(path.to_path_buf());
+        }
+
+        if response_paths.is_empty() {
+            return Err(Box::new(std::io::Error::other(
+                gettext("No JSON file found in %s").replace("%s", &path.to_string_lossy()),
+            )));
+        }
+
+        Ok(FixturesBackend {
+            response_paths,
+            next_response: Cell::new(0),
+        })
+    }
+
+    /// Read the recorded response answering the next request.
+    pub fn next_response(&self) -> Result<Value, Box<dyn Error>> {
+        let index = self.next_response.get();
+        self.next_response
+            .set((index + 1) % self.response_paths.len());
+
+        let response_path = &self.response_paths[index];
+        log::debug!("Answering from the recorded response {:?}", response_path);
+
+        Ok(serde_json::from_slice(&fs::read(response_path)?)?)
+    }
+}
+
+impl RecognitionBackend for FixturesBackend {
+    fn recognize<'a>(&'a self, _signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move { parse_shazam_response(self.next_response()?) })
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::core::fingerprinting::communication::{RequestLocale, recognition_endpoint};
+use crate::core::fingerprinting::shazam_response::{MatchDetails, StreamingLinks};
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::preferences::Preferences;
+use crate::core::recognition_backends::command::CommandBackend;
+use crate::core::recognition_backends::fixtures::FixturesBackend;
+use crate::core::recognition_backends::shazam::ShazamBackend;
+use crate::core::thread_messages::TrackPosition;
+use crate::utils::internationalization::gettext;
+
+/// Name of the backend used when none is configured.
+pub const DEFAULT_RECOGNITION_BACKEND: &str = "shazam";
+
+/// A recognized song, independently of the service that recognized it.
+#[derive(Debug, Clone)]
+pub struct RecognitionResult {
+    pub artist_name: String,
+    pub album_name: Option<String>,
+    pub song_name: String,
+    pub cover_image_url: Option<String>,
+    pub track_key: String,
+    pub release_year: Option<String>,
+    pub genre: Option<String>,
+    /// The lines of the lyrics of the song.
+    pub lyrics: Option<Vec<String>>,
+    /// The International Standard Recording Code of the track.
+    pub isrc: Option<String>,
+    pub streaming_links: StreamingLinks,
+    pub track_position: Option<TrackPosition>,
+    pub match_details: Option<MatchDetails>,
+    /// The response of the service, kept for the JSON output and history.
+    /// Every backend returns responses in the format of Shazam's API, so
+    /// that this is the only field left in that format.
+    pub raw_response: Value,
+}
+
+pub type RecognitionFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<RecognitionResult>, Box<dyn Error>>> + 'a>>;
+
+pub trait RecognitionBackend {
+    /// Recognize a signature, or return None when nothing matched.
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a>;
+}
+
+/// The locale set in the preferences, or else the one of the system.
+pub fn request_locale(preferences: &Preferences) -> RequestLocale {
+    let mut request_locale = RequestLocale::from_system();
+
+    if let Some(locale) = &preferences.request_locale {
+        request_locale.set_locale(locale);
+    }
+    if let Some(timezone) = &preferences.request_timezone {
+        request_locale.timezone = timezone.clone();
+    }
+    if let Some(latitude) = preferences.request_latitude {
+        request_locale.latitude = latitude;
+    }
+    if let Some(longitude) = preferences.request_longitude {
+        request_locale.longitude = longitude;
+    }
+
+    request_locale
+}
+
+/// Build the recognition backend selected in the preferences.
+pub fn get_recognition_backend(
+    preferences: &Preferences,
+) -> Result<Box<dyn RecognitionBackend>, Box<dyn Error>> {
+    let backend_name = preferences
+        .recognition_backend
+        .as_deref()
+        .unwrap_or(DEFAULT_RECOGNITION_BACKEND);
+
+    match backend_name {
+        "shazam" => Ok(Box::new(ShazamBackend::new(
+            &recognition_endpoint(preferences.recognition_endpoint.as_deref()),
+            request_locale(preferences),
+        ))),
+        "fixtures" => match &preferences.recognition_fixtures_path {
+            Some(path) => Ok(Box::new(FixturesBackend::new(path)?)),
+            None => Err(Box::new(std::io::Error::other(gettext(
+                "The fixtures backend requires a path to recorded responses",
+            )))),
+        },
+        "command" => match &preferences.recognition_command {
+            Some(command) => Ok(Box::new(CommandBackend::new(command)?)),
+            None => Err(Box::new(std::io::Error::other(gettext(
+                "The command backend requires a command to run",
+            )))),
+        },
+        _ => Err(Box::new(std::io::Error::other(
+            gettext("Unknown recognition backend: %s").replace("%s", backend_name),
+        ))),
+    }
+}
@@ -0,0 +1,296 @@
+use serde_json::Value;
+use soup::prelude::SessionExt;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::core::fingerprinting::communication::{
+    RequestLocale, recognize_song_from_signature_with_endpoint,
+};
+use crate::core::fingerprinting::shazam_response::{SectionMetadatum, ShazamMatch, ShazamResponse};
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::recognition_backend::{
+    RecognitionBackend, RecognitionFuture, RecognitionResult,
+};
+use crate::core::thread_messages::TrackPosition;
+use crate::utils::internationalization::gettext;
+
+/// Recognizes songs against Shazam's servers, or against a server
+/// imitating their API at another base URL.
+pub struct ShazamBackend {
+    session: soup::Session,
+    endpoint: String,
+    request_locale: RequestLocale,
+}
+
+impl ShazamBackend {
+    pub fn new(endpoint: &str, request_locale: RequestLocale) -> Self {
+        let session = soup::Session::new();
+        session.set_timeout(20);
+        session.set_idle_timeout(2);
+
+        ShazamBackend {
+            session,
+            endpoint: endpoint.to_string(),
+            request_locale,
+        }
+    }
+}
+
+impl RecognitionBackend for ShazamBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move {
+            let json_object = recognize_song_from_signature_with_endpoint(
+                &self.session,
+                &self.endpoint,
+                &self.request_locale,
+                signature,
+            )
+            .await?;
+
+            parse_shazam_response(json_object)
+        })
+    }
+}
+
+/// Parse a track length such as "3:25" or "1:02:10".
+fn parse_track_length(text: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    for component in text.trim().split(':') {
+        seconds = seconds * 60 + component.parse::<u64>().ok()?;
+    }
+
+    Some(Duration::from_secs(seconds))
+}
+
+/// Titles of the metadata of a song, when requested in English.
+const ENGLISH_METADATA_TITLES: [&str; 5] = ["Album", "Label", "Released", "Length", "Duration"];
+
+/// Whether a metadatum looks like a release year, such as "1987".
+fn is_release_year(text: &str) -> bool {
+    text.len() == 4 && text.chars().all(|character| character.is_ascii_digit())
+}
+
+/// Obtain the position of the recording within the recognized track, along
+/// with the length of the track when Shazam gives it among the metadata of
+/// the song.
+fn track_position(
+    shazam_match: Option<&ShazamMatch>,
+    track_length: Option<Duration>,
+) -> Option<TrackPosition> {
+    Some(TrackPosition {
+        offset: Duration::try_from_secs_f64(shazam_match?.offset?).ok()?,
+        track_length,
+    })
+}
+
+/// Extract the album, release year and length of the track from the
+/// metadata of the song.
+fn parse_song_metadata(
+    metadata: &[SectionMetadatum],
+) -> (Option<String>, Option<String>, Option<Duration>) {
+    let mut album_name: Option<String> = None;
+    let mut release_year: Option<String> = None;
+    let mut track_length: Option<Duration> = None;
+
+    let has_english_titles = metadata.iter().any(|metadatum| {
+        metadatum
+            .title
+            .as_deref()
+            .is_some_and(|title| ENGLISH_METADATA_TITLES.contains(&title))
+    });
+
+    // The titles of the metadata are translated when requesting another
+    // language than English (some of them, like "Album" in French, stay the
+    // same), recognize the other fields from their contents then
+    let mut other_texts: Vec<&str> = vec![];
+
+    for metadatum in metadata {
+        let Some(text) = &metadatum.text else {
+            continue;
+        };
+
+        match metadatum.title.as_deref() {
+            Some("Album") if has_english_titles => album_name = Some(text.to_string()),
+            Some("Released") if has_english_titles => release_year = Some(text.to_string()),
+            Some("Length") | Some("Duration") if has_english_titles => {
+                track_length = parse_track_length(text)
+            }
+            _ if is_release_year(text) => {
+                release_year.get_or_insert_with(|| text.to_string());
+            }
+            _ if text.contains(':') && parse_track_length(text).is_some() => {
+                track_length = track_length.or(parse_track_length(text));
+            }
+            _ if !has_english_titles => other_texts.push(text),
+            _ => {}
+        }
+    }
+
+    // Shazam lists the album before the label, and either of them may be
+    // missing: the first remaining field is only known to be the album when
+    // both are present
+    if let [album, _label, ..] = other_texts[..] {
+        album_name = Some(album.to_string());
+    }
+
+    (album_name, release_year, track_length)
+}
+
+/// Extract the recognized song from a response in the format of Shazam's
+/// API, which the other backends reuse. Returns None when nothing matched.
+pub fn parse_shazam_response(
+    json_object: Value,
+) -> Result<Option<RecognitionResult>, Box<dyn Error>> {
+    let response: ShazamResponse =
+        serde_json::from_value(json_object.clone()).map_err(|error| {
+            std::io::Error::other(
+                gettext("Unexpected response from Shazam: %s").replace("%s", &error.to_string()),
+            )
+        })?;
+
+    let Some(track) = &response.track else {
+        return Ok(None);
+    };
+    let (Some(artist_name), Some(song_name), Some(track_key)) =
+        (&track.subtitle, &track.title, &track.key)
+    else {
+        return Ok(None);
+    };
+
+    let (album_name, release_year, track_length) = match track.song_section() {
+        Some(song_section) => parse_song_metadata(&song_section.metadata),
+        None => (None, None, None),
+    };
+
+    Ok(Some(RecognitionResult {
+        artist_name: artist_name.clone(),
+        album_name,
+        song_name: song_name.clone(),
+        cover_image_url: track
+            .images
+            .as_ref()
+            .and_then(|images| images.coverart.clone()),
+        track_key: track_key.clone(),
+        release_year,
+        genre: track
+            .genres
+            .as_ref()
+            .and_then(|genres| genres.primary.clone()),
+        lyrics: track.lyrics().map(|lines| lines.to_vec()),
+        isrc: track.isrc.clone(),
+        streaming_links: track.streaming_links(),
+        track_position: track_position(response.matches.first(), track_length),
+        match_details: response.match_details(),
+        raw_response: json_object,
+    }))
+}
+
+#[cfg(test)]
+fn make_test_response(metadata: Value) -> Value {
+    serde_json::json!({
+        "matches": [{ "id": "1", "offset": 42.5, "timeskew": 0.001, "frequencyskew": 0.0 }],
+        "track": {
+            "key": "12345",
+            "title": "Song Title",
+            "subtitle": "Artist Name",
+            "isrc": "FR0000000001",
+            "images": { "coverart": "https://example.com/cover.jpg" },
+            "genres": { "primary": "Pop" },
+            "sections": [
+                { "type": "SONG", "metadata": metadata },
+                { "type": "LYRICS", "text": ["First line", "Second line"] }
+            ]
+        },
+        "tagid": "ABCDEF",
+        "timestamp": 1700000000000u64,
+        "timezone": "Europe/Paris"
+    })
+}
+
+#[test]
+fn test_parse_shazam_response_match() {
+    let result = parse_shazam_response(make_test_response(serde_json::json!([
+        { "title": "Album", "text": "Album Name" },
+        { "title": "Label", "text": "Label Name" },
+        { "title": "Released", "text": "1987" },
+        { "title": "Length", "text": "3:25" }
+    ])))
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(result.artist_name, "Artist Name");
+    assert_eq!(result.song_name, "Song Title");
+    assert_eq!(result.track_key, "12345");
+    assert_eq!(result.album_name.as_deref(), Some("Album Name"));
+    assert_eq!(result.release_year.as_deref(), Some("1987"));
+    assert_eq!(result.genre.as_deref(), Some("Pop"));
+    assert_eq!(result.isrc.as_deref(), Some("FR0000000001"));
+    assert_eq!(
+        result.cover_image_url.as_deref(),
+        Some("https://example.com/cover.jpg")
+    );
+    assert_eq!(
+        result.lyrics,
+        Some(vec!["First line".to_string(), "Second line".to_string()])
+    );
+
+    let track_position = result.track_position.unwrap();
+    assert_eq!(track_position.offset, Duration::from_secs_f64(42.5));
+    assert_eq!(track_position.track_length, Some(Duration::from_secs(205)));
+}
+
+#[test]
+fn test_parse_shazam_response_no_match() {
+    let json_object = serde_json::json!({
+        "matches": [],
+        "tagid": "ABCDEF",
+        "timestamp": 1700000000000u64,
+        "timezone": "Europe/Paris",
+        "retryms": 4000
+    });
+    assert!(parse_shazam_response(json_object).unwrap().is_none());
+
+    let json_object = serde_json::json!({ "matches": [], "track": { "key": "12345" } });
+    assert!(parse_shazam_response(json_object).unwrap().is_none());
+
+    let json_object = serde_json::json!({ "matches": "unexpected" });
+    assert!(parse_shazam_response(json_object).is_err());
+}
+
+#[test]
+fn test_parse_song_metadata_localized() {
+    let metadatum = |title: &str, text: &str| SectionMetadatum {
+        title: Some(title.to_string()),
+        text: Some(text.to_string()),
+    };
+
+    let (album_name, release_year, track_length) = parse_song_metadata(&[
+        metadatum("Albo", "Nome dell'album"),
+        metadatum("Etichetta", "Nome dell'etichetta"),
+        metadatum("Pubblicazione", "1987"),
+        metadatum("Durata", "1:02:10"),
+    ]);
+    assert_eq!(album_name.as_deref(), Some("Nome dell'album"));
+    assert_eq!(release_year.as_deref(), Some("1987"));
+    assert_eq!(track_length, Some(Duration::from_secs(3730)));
+
+    // Without an album, the label must not be taken for it
+    let (album_name, release_year, track_length) = parse_song_metadata(&[
+        metadatum("Etichetta", "Nome dell'etichetta"),
+        metadatum("Pubblicazione", "1987"),
+    ]);
+    assert_eq!(album_name, None);
+    assert_eq!(release_year.as_deref(), Some("1987"));
+    assert_eq!(track_length, None);
+
+    let result = parse_shazam_response(make_test_response(serde_json::json!([
+        { "title": "Album", "text": "Nom de l'album" },
+        { "title": "Label", "text": "Nom du label" },
+        { "title": "Sortie", "text": "2001" }
+    ])))
+    .unwrap()
+    .unwrap();
+    assert_eq!(result.album_name.as_deref(), Some("Nom de l'album"));
+    assert_eq!(result.release_year.as_deref(), Some("2001"));
+    assert_eq!(result.track_position.unwrap().track_length, None);
+}
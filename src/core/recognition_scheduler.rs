@@ -0,0 +1,257 @@
+//! This module contains code used to avoid recognizing the same song over
+//! and over while it is playing: once a song is recognized, the position of
+//! the recording within the track (as returned by Shazam) and the length of
+//! the track tell when it should end, and requests are paused until then,
+//! unless what is heard stops resembling the recognized recording.
+
+use log::debug;
+use std::time::{Duration, Instant};
+
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::thread_messages::TrackPosition;
+
+/// Length assumed for the tracks whose length is not known, in seconds.
+pub(crate) const TYPICAL_TRACK_LENGTH_SECS: u64 = 210;
+
+/// Requests are never paused for longer than this, in seconds.
+const MAX_PAUSE_SECS: u64 = 600;
+
+/// Number of logarithmically spaced frequency ranges, spanning the
+/// frequency bands of the signatures, in which peaks are counted in order
+/// to describe the timbre of a recording.
+const PROFILE_SIZE: usize = 12;
+const LOWEST_PROFILE_HZ: f32 = 250.0;
+const HIGHEST_PROFILE_HZ: f32 = 5500.0;
+
+/// Distance between the peak profiles of two recordings above which they
+/// are considered as not belonging to the same song, from 0 (same profile)
+/// to 1 (no frequency range in common).
+const MAX_PROFILE_DISTANCE: f32 = 0.4;
+
+type PeakProfile = [f32; PROFILE_SIZE];
+
+struct SentRecording {
+    started_at: Instant,
+    profile: PeakProfile,
+}
+
+/// Decides whether recordings from the microphone should be sent for
+/// recognition, given the song which was recognized last.
+#[derive(Default)]
+pub struct RecognitionScheduler {
+    /// The last recording sent for recognition.
+    sent_recording: Option<SentRecording>,
+
+    /// Expected end of the recognized song, when requests are paused.
+    paused_until: Option<Instant>,
+    reference_profile: PeakProfile,
+}
+
+impl RecognitionScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// To be called when a recording is sent for recognition.
+    pub fn recording_sent(&mut self, signature: &DecodedSignature) {
+        let recording_duration = Duration::from_secs_f32(
+            signature.number_samples as f32 / signature.sample_rate_hz as f32,
+        );
+
+        let now = Instant::now();
+
+        self.sent_recording = Some(SentRecording {
+            started_at: now.checked_sub(recording_duration).unwrap_or(now),
+            profile: peak_profile(signature),
+        });
+    }
+
+    /// To be called when the last recording sent has been recognized, with
+    /// its position within the recognized track.
+    pub fn song_recognized(&mut self, track_position: TrackPosition) {
+        let Some(sent_recording) = self.sent_recording.take() else {
+            return;
+        };
+
+        let track_length = track_position
+            .track_length
+            .unwrap_or(Duration::from_secs(TYPICAL_TRACK_LENGTH_SECS));
+
+        let remaining_duration = track_length
+            .saturating_sub(track_position.offset)
+            .min(Duration::from_secs(MAX_PAUSE_SECS));
+
+        debug!(
+            "Pausing requests for {:?} while the recognized song is playing",
+            remaining_duration.saturating_sub(sent_recording.started_at.elapsed())
+        );
+
+        self.paused_until = Some(sent_recording.started_at + remaining_duration);
+        self.reference_profile = sent_recording.profile;
+    }
+
+    /// To be called when the last recording sent has not been recognized,
+    /// or without its position within the recognized track, so that it is
+    /// not mistaken for the next recording sent.
+    pub fn song_position_unknown(&mut self) {
+        self.sent_recording = None;
+    }
+
+    /// Whether a recording should be sent for recognition. While the
+    /// recognized song is expected to be still playing, this is only the
+    /// case if the recording does not resemble it anymore.
+    pub fn should_recognize(&mut self, signature: &DecodedSignature) -> bool {
+        let Some(paused_until) = self.paused_until else {
+            return true;
+        };
+
+        if Instant::now() >= paused_until {
+            debug!("The recognized song should have ended, resuming requests");
+            self.paused_until = None;
+            return true;
+        }
+
+        let distance = profile_distance(&self.reference_profile, &peak_profile(signature));
+
+        if distance > MAX_PROFILE_DISTANCE {
+            debug!(
+                "The recording differs from the recognized song (distance: {:.2}), resuming requests",
+                distance
+            );
+            self.paused_until = None;
+            return true;
+        }
+
+        false
+    }
+
+    /// Resume requests before the expected end of the recognized song, e.g.
+    /// when the song seems to have stopped.
+    pub fn resume(&mut self) {
+        self.paused_until = None;
+    }
+}
+
+/// Share of the amplitude of the frequency peaks of a signature within each
+/// frequency range.
+fn peak_profile(signature: &DecodedSignature) -> PeakProfile {
+    let mut profile: PeakProfile = [0.0; PROFILE_SIZE];
+
+    let range_ratio_log = (HIGHEST_PROFILE_HZ / LOWEST_PROFILE_HZ).ln() / PROFILE_SIZE as f32;
+
+    for frequency_peak in signature.frequency_band_to_sound_peaks.iter().flatten() {
+        let frequency_hz = frequency_peak.get_frequency_hz(signature.sample_rate_hz);
+
+        let range = ((frequency_hz / LOWEST_PROFILE_HZ).max(1.0).ln() / range_ratio_log) as usize;
+
+        // Peak magnitudes are stored as "ln(power) * 1477.3 + 6144", weight
+        // the peaks by their amplitudes
+
+        profile[range.min(PROFILE_SIZE - 1)] +=
+            ((frequency_peak.peak_magnitude as f32 - 6144.0) / 1477.3 / 2.0).exp();
+    }
+
+    let total_amplitude: f32 = profile.iter().sum();
+
+    if total_amplitude > 0.0 {
+        for share in profile.iter_mut() {
+            *share /= total_amplitude;
+        }
+    }
+
+    profile
+}
+
+/// Total variation distance between two peak profiles.
+fn profile_distance(first: &PeakProfile, second: &PeakProfile) -> f32 {
+    first
+        .iter()
+        .zip(second)
+        .map(|(first_share, second_share)| (first_share - second_share).abs())
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Make a signature with one peak of the same magnitude at each of the
+/// given frequencies, in Hz.
+#[cfg(test)]
+fn make_test_signature(frequencies_hz: &[f32]) -> DecodedSignature {
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+
+    let mut signature = DecodedSignature {
+        sample_rate_hz: 16000,
+        number_samples: 16000 * 12,
+        frequency_band_to_sound_peaks: Default::default(),
+    };
+
+    for (index, frequency_hz) in frequencies_hz.iter().enumerate() {
+        let frequency_band = match *frequency_hz {
+            ..520.0 => 0,
+            ..1450.0 => 1,
+            ..3500.0 => 2,
+            _ => 3,
+        };
+
+        signature.frequency_band_to_sound_peaks[frequency_band].push(FrequencyPeak {
+            fft_pass_number: index as u32 * 10,
+            peak_magnitude: 20000,
+            corrected_peak_frequency_bin: (frequency_hz / (16000.0 / 2.0 / 1024.0 / 64.0)) as u16,
+        });
+    }
+
+    signature
+}
+
+#[test]
+fn test_profile_distance() {
+    let low_signature = make_test_signature(&[300.0, 400.0, 600.0, 800.0]);
+    let high_signature = make_test_signature(&[2000.0, 3000.0, 4000.0, 5000.0]);
+
+    let low_profile = peak_profile(&low_signature);
+    let high_profile = peak_profile(&high_signature);
+
+    assert!((low_profile.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+    // The same recording has the same profile, and recordings without any
+    // frequency range in common are as far as possible
+
+    assert_eq!(profile_distance(&low_profile, &low_profile), 0.0);
+    assert!((profile_distance(&low_profile, &high_profile) - 1.0).abs() < 1e-6);
+    assert!(profile_distance(&low_profile, &high_profile) > MAX_PROFILE_DISTANCE);
+
+    // Recordings sharing most of their frequency ranges are close
+    let similar_signature = make_test_signature(&[300.0, 400.0, 600.0, 800.0, 5000.0]);
+    assert!(
+        profile_distance(&low_profile, &peak_profile(&similar_signature)) < MAX_PROFILE_DISTANCE
+    );
+}
+
+#[test]
+fn test_recognition_scheduler() {
+    let signature = make_test_signature(&[300.0, 400.0, 600.0, 800.0]);
+    let other_signature = make_test_signature(&[2000.0, 3000.0, 4000.0, 5000.0]);
+
+    let track_position = TrackPosition {
+        offset: Duration::from_secs(30),
+        track_length: Some(Duration::from_secs(200)),
+    };
+
+    // Requests are paused while the recognized song is playing, unless the
+    // recording changes
+
+    let mut scheduler = RecognitionScheduler::new();
+    assert!(scheduler.should_recognize(&signature));
+    scheduler.recording_sent(&signature);
+    scheduler.song_recognized(track_position);
+    assert!(!scheduler.should_recognize(&signature));
+    assert!(scheduler.should_recognize(&other_signature));
+
+    // A recording that was not recognized doesn't pause the requests for
+    // a later recognition
+
+    let mut scheduler = RecognitionScheduler::new();
+    scheduler.recording_sent(&signature);
+    scheduler.song_position_unknown();
+    scheduler.song_recognized(track_position);
+    assert!(scheduler.should_recognize(&signature));
+}
@@ -0,0 +1,104 @@
+//! This module contains a minimal HTTP server imitating Shazam's API, which
+//! answers recognition requests with responses recorded in JSON files (see
+//! `FixturesBackend`) and serves cover images from files, so that the whole
+//! application can be run without access to Shazam's servers.
+
+use log::{debug, error, info};
+use soup::prelude::*;
+use std::error::Error;
+use std::path::{Component, Path, PathBuf};
+
+use crate::core::recognition_backends::fixtures::FixturesBackend;
+use crate::utils::internationalization::gettext;
+
+/// Serve the recorded responses found at the given path (a JSON file or a
+/// directory of them) on the given local port, until the process is
+/// stopped. When the path is a directory, the cover images are served from
+/// the files at their paths within it, e.g. "image/thumb/cover.jpg" for
+/// "https://is1-ssl.mzstatic.com/image/thumb/cover.jpg".
+pub fn serve_fixtures(fixtures_path: &str, port: u32) -> Result<(), Box<dyn Error>> {
+    let fixtures = FixturesBackend::new(fixtures_path)?;
+    let covers_directory = Path::new(fixtures_path)
+        .is_dir()
+        .then(|| PathBuf::from(fixtures_path));
+
+    let server = soup::Server::builder().build();
+
+    server.add_handler(None, move |_server, message, path, _query| {
+        debug!("Stand-in server request: {:?} {}", message.method(), path);
+
+        match message.method().as_deref() {
+            Some("POST") if path.starts_with("/discovery/") => {
+                answer_recognition(&fixtures, message)
+            }
+            Some("GET") => answer_cover_image(covers_directory.as_deref(), path, message),
+            _ => message.set_status(405, None),
+        }
+    });
+
+    server.listen_local(port, soup::ServerListenOptions::empty())?;
+
+    for uri in server.uris() {
+        info!(
+            "{}",
+            gettext("Serving recorded responses on %s").replace("%s", &uri.to_str())
+        );
+    }
+
+    glib::MainLoop::new(None, false).run();
+
+    Ok(())
+}
+
+fn answer_recognition(fixtures: &FixturesBackend, message: &soup::ServerMessage) {
+    match fixtures.next_response() {
+        Ok(json_object) => {
+            message.set_status(200, None);
+            message.set_response(
+                Some("application/json"),
+                soup::MemoryUse::Copy,
+                json_object.to_string().as_bytes(),
+            );
+        }
+        Err(error) => {
+            error!("{} {}", gettext("When reading a recorded response:"), error);
+            message.set_status(500, None);
+        }
+    }
+}
+
+fn answer_cover_image(covers_directory: Option<&Path>, path: &str, message: &soup::ServerMessage) {
+    // Do not serve files from outside of the directory
+
+    let relative_path = Path::new(path.trim_start_matches('/'));
+
+    let image_path = match covers_directory {
+        Some(covers_directory)
+            if relative_path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_))) =>
+        {
+            covers_directory.join(relative_path)
+        }
+        _ => {
+            message.set_status(404, None);
+            return;
+        }
+    };
+
+    match std::fs::read(&image_path) {
+        Ok(contents) => {
+            let content_type = match image_path
+                .extension()
+                .and_then(|extension| extension.to_str())
+            {
+                Some("png") => "image/png",
+                Some("jpg") | Some("jpeg") => "image/jpeg",
+                _ => "application/octet-stream",
+            };
+            message.set_status(200, None);
+            message.set_response(Some(content_type), soup::MemoryUse::Copy, &contents);
+        }
+        Err(_) => message.set_status(404, None),
+    }
+}
@@ -1,10 +1,15 @@
 //! This module contains code used from message-based communication between threads.
 
+use crate::core::fingerprinting::recognition_window::RecognitionWindow;
+use crate::core::fingerprinting::shazam_response::{MatchDetails, StreamingLinks};
 use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::fingerprinting::signature_quality::SignatureQuality;
 #[cfg(feature = "gui")]
 use crate::core::preferences::Preferences;
+use crate::core::recognition_scheduler::TYPICAL_TRACK_LENGTH_SECS;
 
 use std::thread;
+use std::time::Duration;
 
 pub fn spawn_big_thread<F, T>(argument: F)
 where
@@ -18,6 +23,27 @@ where
         .unwrap();
 }
 
+/// Position of a recognized recording within its track.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackPosition {
+    /// Position of the start of the recording within the track.
+    pub offset: Duration,
+    pub track_length: Option<Duration>,
+}
+
+impl TrackPosition {
+    /// Share of the track played at the start of the recording, between 0
+    /// and 1, assuming a typical length when the one of the track is not
+    /// known.
+    pub fn progress(&self) -> f64 {
+        let track_length = self
+            .track_length
+            .filter(|length| !length.is_zero())
+            .unwrap_or(Duration::from_secs(TYPICAL_TRACK_LENGTH_SECS));
+        (self.offset.as_secs_f64() / track_length.as_secs_f64()).clamp(0.0, 1.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct SongRecognizedMessage {
     pub artist_name: String,
@@ -30,6 +56,16 @@ pub struct SongRecognizedMessage {
     pub release_year: Option<String>,
     pub genre: Option<String>,
 
+    pub lyrics: Option<Vec<String>>,
+
+    // Used for opening the track in other services
+    pub isrc: Option<String>,
+    pub streaming_links: StreamingLinks,
+
+    // Used for pausing requests until the end of the song
+    pub track_position: Option<TrackPosition>,
+    pub match_details: Option<MatchDetails>,
+
     pub shazam_json: String,
 }
 
@@ -52,15 +88,20 @@ pub enum GUIMessage {
     // under Windows
     DevicesList(Vec<DeviceListItem>),
     #[cfg(feature = "gui")]
-    UpdatePreference(Preferences),
+    UpdatePreference(Box<Preferences>),
     NetworkStatus(bool),  // Is the network reachable?
     RateLimitState(bool), // Are we rate-limited?
+    // Remaining time before a failed request is retried or another request
+    // is sent, reported every second, down to zero
+    RetryCooldown(Duration),
     #[cfg(feature = "gui")]
     WipeSongHistory,
     #[cfg(feature = "gui")]
     AppendToLog(String),
     MicrophoneRecording,
     MicrophoneVolumePercent(f32),
+    // A recording was not sent because its quality is below the threshold
+    SignatureSkipped(SignatureQuality),
     SongRecognized(Box<SongRecognizedMessage>),
 }
 
@@ -69,14 +110,29 @@ pub enum MicrophoneMessage {
     MicrophoneRecordSetDevice(String), // The argument is the audio device name (with an initialization delay)
     RefreshDevices,
     MicrophoneRecordStop,
+    // The recording that was last sent was recognized, at this position
+    // within its track
+    SongPlaying(TrackPosition),
+    // The recording that was last sent was not recognized, or its position
+    // within its track is unknown
+    SongPositionUnknown,
     ProcessingDone,
 }
 
 pub enum ProcessingMessage {
-    ProcessAudioFile(String),
-    ProcessAudioSamples(Vec<f32>), // Prefer to use heap across threads to avoid stack overflow
+    ProcessAudioFile(String, RecognitionWindow),
+    ProcessSignature(Box<DecodedSignature>), // Prefer to use heap across threads to avoid stack overflow
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureOrigin {
+    Microphone,
+    // An audio file chosen by the user
+    File,
 }
 
 pub enum HTTPMessage {
-    RecognizeSignature(Box<DecodedSignature>),
+    // Signatures of one or more excerpts of the same input, tried in turn
+    // until one of them is recognized
+    RecognizeSignatures(Vec<DecodedSignature>, SignatureOrigin),
 }
@@ -0,0 +1,307 @@
+//! This module contains code used to recognize every song played within a
+//! long recording (e.g. a DJ mix or a radio show), by sliding a window over
+//! the whole decoded file and recognizing each of its positions.
+
+use crate::utils::internationalization::gettext;
+use log::{info, warn};
+use serde::Serialize;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::http_task::RetryScheduler;
+use crate::core::recognition_backends::recognition_backend::RecognitionBackend;
+
+/// Length of the audio excerpt sent to Shazam for each window.
+const WINDOW_SECS: usize = 12;
+
+/// Trailing excerpts shorter than this are not worth a request.
+const MIN_LAST_WINDOW_SECS: usize = 3;
+
+pub struct TracklistWindow {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub signature: DecodedSignature,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TracklistEntry {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub artist_name: String,
+    pub song_name: String,
+    pub track_key: String,
+}
+
+/// Decode a whole audio file and generate a signature every `step_secs`
+/// seconds of it, without keeping more than one window of samples in
+/// memory.
+pub fn make_window_signatures(
+    file_path: &str,
+    step_secs: u32,
+) -> Result<Vec<TracklistWindow>, Box<dyn Error>> {
+    let raw_pcm_samples = SignatureGenerator::decode_file(file_path)?;
+
+    Ok(make_window_signatures_from_samples(
+        raw_pcm_samples,
+        step_secs,
+    ))
+}
+
+/// Generate a signature every `step_secs` seconds of 16 KHz mono samples.
+fn make_window_signatures_from_samples(
+    mut raw_pcm_samples: impl Iterator<Item = f32>,
+    step_secs: u32,
+) -> Vec<TracklistWindow> {
+    let window_len = WINDOW_SECS * 16000;
+    let step_len = (step_secs.max(1) as usize) * 16000;
+
+    let mut generator = SignatureGenerator::new();
+    let mut windows: Vec<TracklistWindow> = vec![];
+
+    let mut buffer: Vec<f32> = Vec::with_capacity(window_len);
+    let mut window_start: usize = 0;
+
+    loop {
+        let missing_samples = window_len - buffer.len();
+        buffer.extend(raw_pcm_samples.by_ref().take(missing_samples));
+
+        let is_last_window = buffer.len() < window_len;
+
+        if is_last_window && buffer.len() < MIN_LAST_WINDOW_SECS * 16000 {
+            break;
+        }
+
+        let end_secs = (window_start + buffer.len()) as f32 / 16000.0;
+
+        // Pad the last window to 12 seconds, like when recognizing a
+        // short file
+
+        buffer.resize(window_len, 0.0);
+
+        generator.reset();
+        generator.feed(&buffer);
+
+        windows.push(TracklistWindow {
+            start_secs: window_start as f32 / 16000.0,
+            end_secs,
+            signature: generator.snapshot(),
+        });
+
+        if is_last_window {
+            break;
+        }
+
+        if step_len >= window_len {
+            buffer.clear();
+            raw_pcm_samples
+                .by_ref()
+                .take(step_len - window_len)
+                .for_each(drop);
+        } else {
+            buffer.drain(..step_len);
+        }
+
+        window_start += step_len;
+    }
+
+    windows
+}
+
+/// Recognize each window in turn with `backend`, waiting
+/// `request_interval` between requests in order to avoid being
+/// rate-limited, and return the merged tracklist. Failed requests are
+/// retried like the ones of the other subcommands.
+pub async fn recognize_windows(
+    backend: &dyn RecognitionBackend,
+    windows: &[TracklistWindow],
+    request_interval: Duration,
+) -> Vec<TracklistEntry> {
+    let mut hits: Vec<TracklistEntry> = vec![];
+    let mut retry_scheduler = RetryScheduler::default();
+
+    for (index, window) in windows.iter().enumerate() {
+        if index > 0 {
+            glib::timeout_future(request_interval).await;
+        }
+
+        info!(
+            "{} {}/{} ({})",
+            gettext("Recognizing window"),
+            index + 1,
+            windows.len(),
+            format_timestamp(window.start_secs)
+        );
+
+        let mut retries = 0;
+
+        let result = loop {
+            retry_scheduler.wait_for_cooldown(|_| {}).await;
+
+            let result = backend.recognize(&window.signature).await;
+
+            match &result {
+                Err(error) => {
+                    if retry_scheduler.schedule_retry(error.as_ref(), retries) {
+                        warn!("Retrying a failed request: {}", error);
+                        retries += 1;
+                        continue;
+                    }
+                }
+                Ok(_) => retry_scheduler.request_succeeded(),
+            }
+            break result;
+        };
+
+        match result {
+            Ok(Some(result)) => {
+                hits.push(TracklistEntry {
+                    start_secs: window.start_secs,
+                    end_secs: window.end_secs,
+                    artist_name: result.artist_name,
+                    song_name: result.song_name,
+                    track_key: result.track_key,
+                });
+            }
+            Ok(None) => {}
+            Err(error) => {
+                warn!(
+                    "{} {}: {}",
+                    gettext("Could not recognize window at"),
+                    format_timestamp(window.start_secs),
+                    error
+                );
+            }
+        }
+    }
+
+    merge_hits(hits)
+}
+
+/// Merge consecutive hits for the same song into a single time range.
+pub fn merge_hits(hits: Vec<TracklistEntry>) -> Vec<TracklistEntry> {
+    let mut tracklist: Vec<TracklistEntry> = vec![];
+
+    for hit in hits {
+        match tracklist.last_mut() {
+            Some(last_entry) if last_entry.track_key == hit.track_key => {
+                last_entry.end_secs = last_entry.end_secs.max(hit.end_secs);
+            }
+            _ => {
+                tracklist.push(hit);
+            }
+        }
+    }
+
+    tracklist
+}
+
+/// Format a position in seconds as "HH:MM:SS".
+pub fn format_timestamp(secs: f32) -> String {
+    let secs = secs as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+fn make_test_entry(start_secs: f32, end_secs: f32, track_key: &str) -> TracklistEntry {
+    TracklistEntry {
+        start_secs,
+        end_secs,
+        artist_name: "Artist".to_string(),
+        song_name: format!("Song {}", track_key),
+        track_key: track_key.to_string(),
+    }
+}
+
+#[test]
+fn test_merge_hits() {
+    let tracklist = merge_hits(vec![
+        make_test_entry(0.0, 12.0, "1"),
+        make_test_entry(10.0, 22.0, "1"),
+        make_test_entry(20.0, 32.0, "2"),
+        make_test_entry(30.0, 42.0, "1"),
+        make_test_entry(40.0, 52.0, "1"),
+    ]);
+
+    // Repeats which are not consecutive are kept apart
+
+    let ranges: Vec<(f32, f32, &str)> = tracklist
+        .iter()
+        .map(|entry| (entry.start_secs, entry.end_secs, entry.track_key.as_str()))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![(0.0, 22.0, "1"), (20.0, 32.0, "2"), (30.0, 52.0, "1")]
+    );
+
+    assert!(merge_hits(vec![]).is_empty());
+}
+
+#[test]
+fn test_window_boundaries() {
+    // The buffers of the generator need a larger stack than the one of
+    // test threads in debug builds
+
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(|| {
+            let window_ranges = |duration_secs: usize, step_secs: u32| -> Vec<(f32, f32)> {
+                let samples = (0..duration_secs * 16000)
+                    .map(|index| (index as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin());
+
+                make_window_signatures_from_samples(samples, step_secs)
+                    .iter()
+                    .map(|window| {
+                        assert_eq!(
+                            window.signature.number_samples,
+                            (WINDOW_SECS * 16000) as u32
+                        );
+                        (window.start_secs, window.end_secs)
+                    })
+                    .collect()
+            };
+
+            // The trailing window is kept, shorter, when it lasts at least
+            // three seconds
+
+            assert_eq!(
+                window_ranges(30, 10),
+                vec![(0.0, 12.0), (10.0, 22.0), (20.0, 30.0)]
+            );
+            assert_eq!(
+                window_ranges(23, 10),
+                vec![(0.0, 12.0), (10.0, 22.0), (20.0, 23.0)]
+            );
+            assert_eq!(window_ranges(22, 10), vec![(0.0, 12.0), (10.0, 22.0)]);
+
+            // Steps longer than a window skip the samples in between
+
+            assert_eq!(
+                window_ranges(50, 20),
+                vec![(0.0, 12.0), (20.0, 32.0), (40.0, 50.0)]
+            );
+
+            // Files shorter than a window give a single padded one
+
+            assert_eq!(window_ranges(5, 10), vec![(0.0, 5.0)]);
+            assert!(window_ranges(2, 10).is_empty());
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0.0), "00:00:00");
+    assert_eq!(format_timestamp(59.9), "00:00:59");
+    assert_eq!(format_timestamp(3725.0), "01:02:05");
+    assert_eq!(format_timestamp(36000.0 + 61.0), "10:01:01");
+}
@@ -16,6 +16,7 @@ use crate::gui::song_history_interface::FavoritesInterface;
 use crate::core::preferences::PreferencesInterface;
 use crate::gui::history_entry::HistoryEntry;
 use crate::gui::song_history_interface::{RecognitionHistoryInterface, SongRecordInterface};
+use crate::utils::internationalization::gettext;
 
 pub struct ContextMenuUtil;
 
@@ -49,8 +50,10 @@ impl ContextMenuUtil {
                     let faved_model: gio::Menu =
                         builder.object("history_context_model_faved").unwrap();
                     if favorites.borrow().is_favorite(record.get_song()) {
+                        Self::fill_links_section(&builder, "history_links_section_faved", &record);
                         popover_menu.set_menu_model(Some(&faved_model));
                     } else {
+                        Self::fill_links_section(&builder, "history_links_section", &record);
                         popover_menu.set_menu_model(Some(&unfaved_model));
                     }
 
@@ -114,8 +117,14 @@ impl ContextMenuUtil {
                         let faved_model: gio::Menu =
                             builder.object("history_context_model_faved").unwrap();
                         if favorites.borrow().is_favorite(record.get_song()) {
+                            Self::fill_links_section(
+                                &builder,
+                                "history_links_section_faved",
+                                &record,
+                            );
                             popover_menu.set_menu_model(Some(&faved_model));
                         } else {
+                            Self::fill_links_section(&builder, "history_links_section", &record);
                             popover_menu.set_menu_model(Some(&unfaved_model));
                         }
 
@@ -156,6 +165,35 @@ impl ContextMenuUtil {
         }); */
     }
 
+    /// List the services the track of the entry can be opened in, as
+    /// items of the given section of the context menu.
+    fn fill_links_section(builder: &gtk::Builder, section_id: &str, record: &HistoryEntry) {
+        let section: gio::Menu = builder.object(section_id).unwrap();
+        section.remove_all();
+
+        let links = [
+            ("Shazam", record.shazam_link()),
+            ("Apple Music", record.apple_music_link()),
+            ("Spotify", record.spotify_link()),
+            ("Deezer", record.deezer_link()),
+        ];
+
+        for (service_name, link) in links {
+            if let Some(link) = link.filter(|link| !link.is_empty()) {
+                let item = gio::MenuItem::new(
+                    Some(&gettext("Open in %s").replace("%s", service_name)),
+                    None,
+                );
+                item.set_action_and_target_value(
+                    Some("history-menu.open-link"),
+                    Some(&link.to_variant()),
+                );
+                item.set_icon(&gio::ThemedIcon::new("external-link-symbolic"));
+                section.append_item(&item);
+            }
+        }
+    }
+
     pub fn bind_actions(
         window: adw::ApplicationWindow,
         popover_menu: gtk::PopoverMenu,
@@ -218,6 +256,19 @@ impl ContextMenuUtil {
             })
             .build();
 
+        let item = ctx_selected_item.clone();
+        let action_copy_lyrics = gio::ActionEntry::builder("copy-lyrics")
+            .activate(move |_, _, _| {
+                if let Some(entry) = &*item.borrow()
+                    && let Some(display) = gdk::Display::default()
+                {
+                    if let Some(lyrics) = entry.lyrics() {
+                        display.clipboard().set(&lyrics);
+                    }
+                }
+            })
+            .build();
+
         let item = ctx_selected_item.clone();
         let preferences = preferences_interface.clone();
         let action_search_youtube = gio::ActionEntry::builder("search-on-youtube")
@@ -258,6 +309,27 @@ impl ContextMenuUtil {
             ))
             .build();
 
+        let action_open_link = gio::ActionEntry::builder("open-link")
+            .parameter_type(Some(glib::VariantTy::STRING))
+            .activate(clone!(
+                #[weak]
+                window,
+                move |_, _, parameter| {
+                    if let Some(link) = parameter.and_then(|parameter| parameter.get::<String>()) {
+                        glib::spawn_future_local(async move {
+                            info!("Launching URL: {}", link);
+                            if let Err(err) = gtk::UriLauncher::new(&link)
+                                .launch_future(Some(&window))
+                                .await
+                            {
+                                error!("Could not launch URL {}: {:?}", link, err);
+                            }
+                        });
+                    }
+                }
+            ))
+            .build();
+
         let item = ctx_selected_item.clone();
         let favorites = favorites_interface.clone();
         let action_add_favorites = gio::ActionEntry::builder("add-to-favorites")
@@ -304,12 +376,29 @@ impl ContextMenuUtil {
             action_copy_artist,
             action_copy_track,
             action_copy_album,
+            action_copy_lyrics,
             action_add_favorites,
             action_remove_history,
             action_remove_favorites,
             action_search_youtube,
+            action_open_link,
         ]);
         window.insert_action_group("history-menu", Some(&actions));
+
+        // The item copying the lyrics is hidden while disabled, for the
+        // entries without any
+        let action_copy_lyrics = actions
+            .lookup_action("copy-lyrics")
+            .and_downcast::<gio::SimpleAction>()
+            .unwrap();
+        let item = ctx_selected_item.clone();
+        popover_menu.connect_show(move |_| {
+            action_copy_lyrics.set_enabled(
+                item.borrow()
+                    .as_ref()
+                    .is_some_and(|entry| entry.lyrics().is_some_and(|lyrics| !lyrics.is_empty())),
+            );
+        });
     }
 
     // See:
@@ -21,6 +21,26 @@ pub struct HistoryEntry {
     genre: RefCell<Option<String>>,
     #[property(construct_only, get)]
     recognition_date: RefCell<String>,
+    #[property(construct_only, get)]
+    offset_secs: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    time_skew: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    frequency_skew: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    confidence: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    lyrics: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    isrc: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    shazam_link: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    apple_music_link: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    spotify_link: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    deezer_link: RefCell<Option<String>>,
 }
 
 // The central trait for subclassing a GObject
@@ -15,6 +15,16 @@ impl HistoryEntry {
             .property("release_year", &song.release_year)
             .property("genre", &song.genre)
             .property("recognition_date", &song.recognition_date)
+            .property("offset_secs", &song.offset_secs)
+            .property("time_skew", &song.time_skew)
+            .property("frequency_skew", &song.frequency_skew)
+            .property("confidence", &song.confidence)
+            .property("lyrics", &song.lyrics)
+            .property("isrc", &song.isrc)
+            .property("shazam_link", &song.shazam_link)
+            .property("apple_music_link", &song.apple_music_link)
+            .property("spotify_link", &song.spotify_link)
+            .property("deezer_link", &song.deezer_link)
             .build()
 
         /*
@@ -43,6 +53,16 @@ impl HistoryEntry {
             release_year: self.release_year(),
             genre: self.genre(),
             recognition_date: self.recognition_date(),
+            offset_secs: self.offset_secs(),
+            time_skew: self.time_skew(),
+            frequency_skew: self.frequency_skew(),
+            confidence: self.confidence(),
+            lyrics: self.lyrics(),
+            isrc: self.isrc(),
+            shazam_link: self.shazam_link(),
+            apple_music_link: self.apple_music_link(),
+            spotify_link: self.spotify_link(),
+            deezer_link: self.deezer_link(),
         }
     }
 
@@ -1,20 +1,27 @@
+use crate::utils::internationalization::gettext;
 use adw::prelude::*;
 use chrono::Local;
-use gettextrs::gettext;
 use log::{debug, error, info, trace};
 #[cfg(all(target_os = "linux", feature = "mpris"))]
 use mpris_server::PlaybackStatus;
 use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
 use serde_json::json;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::error::Error;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
+use crate::core::fingerprinting::communication::{cover_endpoint, recognition_endpoint};
+use crate::core::fingerprinting::recognition_window::RecognitionWindow;
+use crate::core::fingerprinting::signature_quality::DEFAULT_MIN_QUALITY_PERCENT;
 use crate::core::http_task::http_task;
 use crate::core::logging::Logging;
 use crate::core::microphone_thread::microphone_thread;
 use crate::core::processing_thread::processing_thread;
+use crate::core::recognition_backends::recognition_backend::{
+    get_recognition_backend, request_locale,
+};
+use crate::core::recognition_backends::shazam::ShazamBackend;
 use crate::core::thread_messages::{GUIMessage::*, *};
 
 use crate::gui::song_history_interface::FavoritesInterface;
@@ -140,6 +147,13 @@ impl App {
         let request_interval_value: gtk::Adjustment = builder.object("interval_value").unwrap();
         request_interval_value.set_value(old_preferences.request_interval_secs_v3.unwrap() as f64);
 
+        let min_quality_value: gtk::Adjustment = builder.object("min_quality_value").unwrap();
+        min_quality_value.set_value(
+            old_preferences
+                .min_quality_percent
+                .unwrap_or(DEFAULT_MIN_QUALITY_PERCENT) as f64,
+        );
+
         App {
             builder,
 
@@ -208,7 +222,10 @@ impl App {
                     let file_path_string = file_path.into_os_string().into_string().unwrap();
 
                     processing_tx
-                        .try_send(ProcessingMessage::ProcessAudioFile(file_path_string))
+                        .try_send(ProcessingMessage::ProcessAudioFile(
+                            file_path_string,
+                            RecognitionWindow::default(),
+                        ))
                         .unwrap();
                 }
             }
@@ -424,7 +441,7 @@ impl App {
                 let mut new_preference = Preferences::new();
                 new_preference.website_search_text = Some(entry_row.text().to_string());
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
             }
 
@@ -442,7 +459,7 @@ impl App {
                 let mut new_preference = Preferences::new();
                 new_preference.website_search_url = Some(entry_row.text().to_string());
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
             }
 
@@ -652,7 +669,7 @@ impl App {
                 let mut new_preference = Preferences::new();
                 new_preference.current_device_name = Some(device_name.to_string());
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
 
                 // Should we start recording yet? (will depend of the possible
@@ -681,7 +698,20 @@ impl App {
             let mut new_preference = Preferences::new();
             new_preference.request_interval_secs_v3 = Some(adjustment.value() as u64);
             gui_tx
-                .try_send(GUIMessage::UpdatePreference(new_preference))
+                .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
+                .unwrap();
+            None
+        });
+
+        let gui_tx = gui_tx_shared.clone();
+
+        builder_scope.add_callback("min_quality_changed", move |values| {
+            let adjustment = values[0].get::<gtk::Adjustment>().unwrap();
+            debug!("Minimal recording quality set to: {}", adjustment.value());
+            let mut new_preference = Preferences::new();
+            new_preference.min_quality_percent = Some(adjustment.value() as u64);
+            gui_tx
+                .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                 .unwrap();
             None
         });
@@ -734,7 +764,29 @@ impl App {
         let http_rx = self.http_rx.clone();
         let gui_tx = self.gui_tx.clone();
         let microphone_tx = self.microphone_tx.clone();
-        glib::spawn_future_local(http_task(http_rx, gui_tx, microphone_tx));
+        let recognition_backend =
+            get_recognition_backend(&self.old_preferences).unwrap_or_else(|error| {
+                error!(
+                    "{} {}",
+                    gettext("When setting up the recognition backend:"),
+                    error
+                );
+                // Keep the configured endpoint and locale for Shazam
+                Box::new(ShazamBackend::new(
+                    &recognition_endpoint(self.old_preferences.recognition_endpoint.as_deref()),
+                    request_locale(&self.old_preferences),
+                ))
+            });
+        let cover_endpoint = cover_endpoint(self.old_preferences.cover_endpoint.as_deref());
+        let preferences_interface = self.preferences_interface.clone();
+        glib::spawn_future_local(http_task(
+            http_rx,
+            gui_tx,
+            microphone_tx,
+            preferences_interface,
+            recognition_backend,
+            cover_endpoint,
+        ));
 
         let gui_rx = self.gui_rx.clone();
         let preferences_interface_ptr = self.preferences_interface.clone();
@@ -760,8 +812,14 @@ impl App {
             self.builder.object("results_section").unwrap();
         let no_network_message: gtk::Label = self.builder.object("no_network_message").unwrap();
         let rate_limited_message: gtk::Label = self.builder.object("rate_limited_message").unwrap();
+        let low_quality_message: gtk::Label = self.builder.object("low_quality_message").unwrap();
+        let retry_cooldown_message: gtk::Label =
+            self.builder.object("retry_cooldown_message").unwrap();
         let results_image: gtk::Image = self.builder.object("results_image").unwrap();
         let results_label: gtk::Label = self.builder.object("results_label").unwrap();
+        let lyrics_scrollview: gtk::ScrolledWindow =
+            self.builder.object("lyrics_scrollview").unwrap();
+        let lyrics_label: gtk::Label = self.builder.object("lyrics_label").unwrap();
         let loopback_switch: adw::SwitchRow = self.builder.object("loopback_switch").unwrap();
 
         #[cfg(target_os = "linux")]
@@ -772,6 +830,21 @@ impl App {
 
         microphone_switch.set_active(set_recording);
 
+        // Scroll the lyrics to about the part being played once they have
+        // been laid out, which changes the bounds of the adjustment
+
+        let lyrics_scroll_fraction: Rc<Cell<Option<f64>>> = Rc::new(Cell::new(None));
+        let lyrics_scroll_fraction_2 = lyrics_scroll_fraction.clone();
+
+        lyrics_scrollview
+            .vadjustment()
+            .connect_changed(move |adjustment| {
+                if let Some(fraction) = lyrics_scroll_fraction_2.take() {
+                    adjustment
+                        .set_value(fraction * adjustment.upper() - adjustment.page_size() / 2.0);
+                }
+            });
+
         let song_history_interface = self.song_history_interface.clone();
         let _old_preferences = self.old_preferences.clone();
         let ctx_buffered_log = self.ctx_buffered_log.clone();
@@ -837,6 +910,8 @@ impl App {
                                 "track_key": msg.track_key,
                                 "release_year": msg.release_year,
                                 "genre": msg.genre,
+                                "match_details": msg.match_details,
+                                "streaming_links": msg.streaming_links,
                                 "shazam_json": msg.shazam_json,
                             })
                         );
@@ -848,6 +923,7 @@ impl App {
                         ErrorMessage(_) | NetworkStatus(_) | SongRecognized(_) => {
                             recognize_file_row.set_sensitive(true);
                             spinner_row.set_visible(false);
+                            low_quality_message.set_visible(false);
                         }
                         _ => {}
                     }
@@ -857,7 +933,7 @@ impl App {
                             preferences_interface_ptr
                                 .lock()
                                 .unwrap()
-                                .update(new_preference);
+                                .update(*new_preference);
                             #[cfg(all(target_os = "linux", feature = "mpris"))]
                             if _enable_mpris_cli {
                                 let mpris_enabled = preferences_interface_ptr
@@ -916,6 +992,9 @@ impl App {
                                 }
                             }
                         }
+                        SignatureSkipped(_) => {
+                            low_quality_message.set_visible(true);
+                        }
                         RateLimitState(is_rate_limited) => {
                             if is_rate_limited && !rate_limited_message.is_visible() {
                                 Self::notify_network_error(
@@ -927,6 +1006,13 @@ impl App {
                             }
                             rate_limited_message.set_visible(is_rate_limited);
                         }
+                        RetryCooldown(remaining) => {
+                            retry_cooldown_message.set_label(
+                                &gettext("Retrying in %s seconds")
+                                    .replace("%s", &remaining.as_secs_f32().ceil().to_string()),
+                            );
+                            retry_cooldown_message.set_visible(!remaining.is_zero());
+                        }
                         NetworkStatus(network_is_reachable) => {
                             if !network_is_reachable && !no_network_message.is_visible() {
                                 Self::notify_network_error(
@@ -1001,6 +1087,18 @@ impl App {
                                     results_image.set_visible(false);
                                 }
 
+                                if let Some(ref lyrics) = message.lyrics {
+                                    lyrics_scroll_fraction.set(
+                                        message.track_position.map(|position| position.progress()),
+                                    );
+                                    lyrics_label.set_label(&lyrics.join("\n"));
+                                    lyrics_scrollview.vadjustment().set_value(0.0);
+                                    lyrics_scrollview.set_visible(true);
+                                } else {
+                                    lyrics_scrollview.set_visible(false);
+                                    lyrics_label.set_label("");
+                                }
+
                                 #[cfg(all(target_os = "linux", feature = "mpris"))]
                                 if preferences_interface_ptr
                                     .lock()
@@ -1031,7 +1129,11 @@ impl App {
                                     release_year: Some(message.release_year.unwrap_or_default()),
                                     genre: Some(message.genre.unwrap_or_default()),
                                     recognition_date: Local::now().format("%c").to_string(),
-                                };
+                                    ..Default::default()
+                                }
+                                .with_match_details(message.match_details)
+                                .with_lyrics(message.lyrics.as_deref())
+                                .with_streaming_links(message.isrc, message.streaming_links);
 
                                 if preferences_interface_ptr
                                     .lock()
@@ -1255,7 +1357,10 @@ impl App {
                             spinner_row.set_visible(true);
 
                             processing_tx
-                                .try_send(ProcessingMessage::ProcessAudioFile(path_str))
+                                .try_send(ProcessingMessage::ProcessAudioFile(
+                                    path_str,
+                                    RecognitionWindow::default(),
+                                ))
                                 .unwrap();
                         }
                         Err(error) => {
@@ -1389,7 +1494,7 @@ impl App {
                 let mut new_preference: Preferences = Preferences::new();
                 new_preference.enable_mpris_v2 = Some(new_state);
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
             })
             .build();
@@ -1412,7 +1517,7 @@ impl App {
                 let mut new_preference: Preferences = Preferences::new();
                 new_preference.enable_notifications = Some(new_state);
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
             })
             .build();
@@ -1442,7 +1547,7 @@ impl App {
                     let mut new_preference: Preferences = Preferences::new();
                     new_preference.enable_systray = Some(new_state);
                     _gui_tx
-                        .try_send(GUIMessage::UpdatePreference(new_preference))
+                        .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                         .unwrap();
                 },
             )
@@ -1461,7 +1566,30 @@ impl App {
                 let mut new_preference: Preferences = Preferences::new();
                 new_preference.no_duplicates = Some(new_state);
                 gui_tx
-                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
+                    .unwrap();
+            })
+            .build();
+
+        let gui_tx = self.gui_tx.clone();
+
+        let action_song_changes_setting = gio::ActionEntry::builder("song-changes-setting")
+            .state(
+                self.old_preferences
+                    .detect_song_changes
+                    .unwrap()
+                    .to_variant(),
+            )
+            .activate(move |_, action, _| {
+                let state = action.state().unwrap();
+                let action_state: bool = state.get().unwrap();
+                let new_state = !action_state; // toggle
+                action.set_state(&new_state.to_variant());
+
+                let mut new_preference: Preferences = Preferences::new();
+                new_preference.detect_song_changes = Some(new_state);
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(Box::new(new_preference)))
                     .unwrap();
             })
             .build();
@@ -1506,6 +1634,7 @@ impl App {
             #[cfg(target_os = "linux")]
             action_systray_setting,
             action_no_dupes_setting,
+            action_song_changes_setting,
             action_refresh_devices,
             action_close,
             action_show_menu,
@@ -3,7 +3,7 @@
 /// GTK-rs GUI of SongRec and the filesystem while using the GUI.
 use crate::gui::history_entry::HistoryEntry;
 use crate::utils::csv_song_history::{HasSong, Song, SongHistoryRecord};
-use gettextrs::gettext;
+use crate::utils::internationalization::gettext;
 use gtk::prelude::*;
 use log::error;
 use std::collections::HashSet;
@@ -0,0 +1,76 @@
+//! SongRec's audio fingerprinting and recognition core, usable as a library:
+//! generating Shazam signatures from audio files or raw samples, encoding and
+//! decoding them, comparing them offline, and recognizing them against
+//! Shazam's servers.
+//!
+//! The items re-exported at the root of this crate make up its public API.
+//! The modules below are only public for the needs of the `songrec` binary
+//! and may change at any time. The command-line interface, audio capture,
+//! GUI, MPRIS and tray icon code belongs to the binary, which is only built
+//! with the `cli` feature, and translations of messages are only enabled
+//! with the `i18n` feature, so that depending on this crate with
+//! `default-features = false` only pulls the fingerprinting dependencies.
+//!
+//! ```no_run
+//! use songrec::{SignatureGenerator, recognize_song_from_signature, soup};
+//!
+//! let signature = SignatureGenerator::make_signature_from_file("sound_file.mp3")?;
+//! println!("{}", signature.encode_to_uri()?);
+//!
+//! let session = soup::Session::new();
+//! glib::MainContext::default().block_on(async {
+//!     let response = recognize_song_from_signature(&session, &signature).await?;
+//!     println!("{}", response["track"]["title"]);
+//!     Ok::<(), Box<dyn std::error::Error>>(())
+//! })?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub use crate::core::fingerprinting::algorithm::SignatureGenerator;
+pub use crate::core::fingerprinting::communication::{
+    RequestLocale, obtain_raw_cover_image, obtain_raw_cover_image_with_endpoint,
+    recognize_song_from_signature, recognize_song_from_signature_with_endpoint,
+};
+pub use crate::core::fingerprinting::local_database::{LocalDatabase, LocalMatch};
+pub use crate::core::fingerprinting::shazam_response::{
+    MatchDetails, ShazamResponse, StreamingLinks,
+};
+pub use crate::core::fingerprinting::signature_format::{
+    DecodedSignature, FrequencyBand, FrequencyPeak, SignatureError,
+};
+pub use crate::core::fingerprinting::signature_quality::SignatureQuality;
+pub use crate::core::fingerprinting::signature_similarity::SignatureSimilarity;
+
+/// The HTTP client library used for communicating with Shazam's servers.
+pub use soup;
+
+#[doc(hidden)]
+pub mod core {
+    pub mod fingerprinting {
+        pub mod algorithm;
+        pub mod communication;
+        mod hanning;
+        pub mod local_database;
+        pub mod recognition_window;
+        pub mod shazam_response;
+        pub mod signature_editing;
+        pub mod signature_format;
+        pub mod signature_inspection;
+        pub mod signature_quality;
+        pub mod signature_similarity;
+        mod user_agent;
+    }
+}
+
+#[doc(hidden)]
+pub mod utils {
+    pub mod internationalization;
+}
+
+#[doc(hidden)]
+pub mod plugins {
+    #[cfg(feature = "ffmpeg")]
+    pub mod ffmpeg_wrapper;
+    #[cfg(feature = "opus")]
+    pub mod opus_decoder;
+}
@@ -1,14 +1,20 @@
 #![cfg_attr(feature = "gui", windows_subsystem = "windows")]
 
-pub mod cli_main;
+mod cli_main;
 
 mod core {
+    pub use songrec::core::fingerprinting;
+
     pub mod http_task;
     pub mod logging;
     pub mod microphone_thread;
+    pub mod music_activity;
     pub mod preferences;
     pub mod processing_thread;
+    pub mod recognition_scheduler;
+    pub mod stand_in_server;
     pub mod thread_messages;
+    pub mod tracklist;
 
     mod audio_controllers {
         pub mod audio_backend;
@@ -17,12 +23,11 @@ mod core {
         pub mod pulseaudio;
     }
 
-    pub mod fingerprinting {
-        pub mod algorithm;
-        pub mod communication;
-        mod hanning;
-        pub mod signature_format;
-        mod user_agent;
+    pub mod recognition_backends {
+        pub mod command;
+        pub mod fixtures;
+        pub mod recognition_backend;
+        pub mod shazam;
     }
 }
 
@@ -37,14 +42,13 @@ mod gui {
 }
 
 mod utils {
+    pub use songrec::utils::internationalization;
+
     pub mod csv_song_history;
     pub mod filesystem_operations;
-    pub mod internationalization;
 }
 
 mod plugins {
-    #[cfg(feature = "ffmpeg")]
-    pub mod ffmpeg_wrapper;
     #[cfg(feature = "gui")]
     #[cfg(target_os = "linux")]
     pub mod ksni;
@@ -52,21 +56,38 @@ mod plugins {
     pub mod mpris_player;
 }
 
-use crate::core::fingerprinting::algorithm::SignatureGenerator;
-use crate::core::fingerprinting::communication::recognize_song_from_signature;
-use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::fingerprinting::local_database::collect_files;
+use crate::core::fingerprinting::recognition_window::{RecognitionWindow, WindowStrategy};
+use crate::core::fingerprinting::signature_inspection::{
+    SignatureInspection, render_constellation_plot, write_peaks_csv,
+};
+#[cfg(not(feature = "gui"))]
+use crate::core::fingerprinting::signature_quality::DEFAULT_MIN_QUALITY_PERCENT;
 
 use crate::cli_main::{CLIOutputType, CLIParameters, cli_main};
 use crate::core::logging::Logging;
+use crate::core::microphone_thread::RawPcmFormat;
+use crate::core::preferences::Preferences;
+#[cfg(not(feature = "gui"))]
+use crate::core::recognition_backends::recognition_backend::DEFAULT_RECOGNITION_BACKEND;
+use crate::core::recognition_backends::recognition_backend::get_recognition_backend;
+use crate::core::stand_in_server::serve_fixtures;
+use crate::core::tracklist::{format_timestamp, make_window_signatures, recognize_windows};
 #[cfg(feature = "gui")]
 use crate::gui::main_window::gui_main;
-use crate::utils::internationalization::setup_internationalization;
+use crate::utils::internationalization::{gettext, setup_internationalization};
+use songrec::{
+    DecodedSignature, LocalDatabase, SignatureError, SignatureGenerator,
+    recognize_song_from_signature,
+};
 
-use clap::{Arg, ArgAction, Command, command};
-use gettextrs::gettext;
-use log::debug;
+use clap::{Arg, ArgAction, ArgMatches, Command, command};
+use log::{debug, info, warn};
 use soup::prelude::SessionExt;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
 macro_rules! base_app {
     () => {
@@ -111,6 +132,13 @@ SongRec {version}
                         .long("audio-device")
                         .help(gettext("Specify the audio device to use"))
                 )
+                .arg(
+                    Arg::new("raw-pcm")
+                        .long("raw-pcm")
+                        .value_name("RATE:CHANNELS:FORMAT")
+                        .conflicts_with_all(["list-devices", "audio-device"])
+                        .help(gettext("Read raw PCM samples from the standard input instead of using mic input, e.g. 44100:2:s16le (formats: u8, s16le, s16be, s24le, s32le, f32le)"))
+                )
                 .arg(
                     Arg::new("request-interval")
                         .short('i')
@@ -119,6 +147,75 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -134,6 +231,19 @@ SongRec {version}
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("lyrics")
+                        .long("lyrics")
+                        .conflicts_with_all(["json", "csv"])
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the lyrics of recognized songs after their names, when Shazam knows them"))
+                )
+                .arg(
+                    Arg::new("detect-changes")
+                        .long("detect-changes")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Recognize only when music starts or changes, instead of at a fixed interval"))
+                )
                 .arg(
                     Arg::new("disable-mpris")
                         .long("disable-mpris")
@@ -158,6 +268,13 @@ SongRec {version}
                         .action(ArgAction::Set)
                         .help(gettext("Specify the audio device to use"))
                 )
+                .arg(
+                    Arg::new("raw-pcm")
+                        .long("raw-pcm")
+                        .value_name("RATE:CHANNELS:FORMAT")
+                        .conflicts_with_all(["list-devices", "audio-device", "input_file"])
+                        .help(gettext("Read raw PCM samples from the standard input instead of using mic input, e.g. 44100:2:s16le (formats: u8, s16le, s16be, s24le, s32le, f32le)"))
+                )
                 .arg(
                     Arg::new("request-interval")
                         .short('i')
@@ -166,6 +283,75 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -181,6 +367,37 @@ SongRec {version}
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("lyrics")
+                        .long("lyrics")
+                        .conflicts_with_all(["json", "csv"])
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the lyrics of recognized songs after their names, when Shazam knows them"))
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .requires("input_file")
+                        .value_parser(clap::value_parser!(u64))
+                        .conflicts_with("strategy")
+                        .help(gettext("Position of the excerpt of the file to recognize, in seconds"))
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .requires("input_file")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(1..=12))
+                        .help(gettext("Length of the excerpt of the file to recognize, in seconds (at most 12)"))
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .long("strategy")
+                        .requires("input_file")
+                        .default_value("middle")
+                        .value_parser(["middle", "start", "end", "multi"])
+                        .help(gettext("Take the excerpt of the file to recognize from its middle, start or end, or try several excerpts in turn until a match is found (multi)"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(false)
@@ -220,10 +437,100 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("min-quality")
+                        .long("min-quality")
+                        .value_name("PERCENT")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64).range(0..=100))
+                        .help(gettext("Don't send recordings whose quality (the amount of music they seem to contain) is below this percentage"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("cover-endpoint")
+                        .long("cover-endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL to obtain cover images from, rather than the one of the image URLs returned by Shazam (defaults to $SONGREC_COVER_ENDPOINT if set)"))
+                )
         )
         .subcommand(
             Command::new("audio-file-to-fingerprint")
                 .about(gettext("Generate a Shazam fingerprint from a sound file, and print it to the standard output."))
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .value_parser(clap::value_parser!(u64))
+                        .conflicts_with("strategy")
+                        .help(gettext("Position of the excerpt of the file to recognize, in seconds"))
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(1..=12))
+                        .help(gettext("Length of the excerpt of the file to recognize, in seconds (at most 12)"))
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .long("strategy")
+                        .default_value("middle")
+                        .value_parser(["middle", "start", "end", "multi"])
+                        .help(gettext("Take the excerpt of the file to recognize from its middle, start or end, or try several excerpts in turn until a match is found (multi)"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(true)
@@ -239,6 +546,267 @@ SongRec {version}
                         .help(gettext("The data-URI Shazam fingerprint to recognize."))
                 )
         )
+        .subcommand(
+            Command::new("convert-fingerprint")
+                .about(gettext("Convert a Shazam fingerprint between the data-URI, JSON and TOML formats, and print it to the standard output."))
+                .arg(
+                    Arg::new("to")
+                        .short('t')
+                        .long("to")
+                        .default_value("json")
+                        .value_parser(["uri", "json", "toml"])
+                        .help(gettext("Output format"))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint, or file containing a binary, data-URI, JSON or TOML fingerprint, to convert."))
+                )
+        )
+        .subcommand(
+            Command::new("inspect-fingerprint")
+                .about(gettext("Print the header fields, checksum validity and frequency peaks of a Shazam fingerprint, for debugging purposes."))
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .default_value("text")
+                        .value_parser(["text", "json", "csv"])
+                        .help(gettext("Output format: a readable report, the full report in JSON, or the frequency peaks in CSV"))
+                )
+                .arg(
+                    Arg::new("plot")
+                        .short('p')
+                        .long("plot")
+                        .help(gettext("Render the frequency peaks as a constellation plot to this PNG file"))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint, or binary fingerprint file, to inspect."))
+                )
+        )
+        .subcommand(
+            Command::new("slice-fingerprint")
+                .about(gettext("Cut a data-URI Shazam fingerprint to a time range, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("start")
+                        .short('s')
+                        .long("start")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("Start of the range to keep, in seconds"))
+                )
+                .arg(
+                    Arg::new("end")
+                        .short('e')
+                        .long("end")
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("End of the range to keep, in seconds (defaults to the end of the fingerprint)"))
+                )
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to cut."))
+                )
+        )
+        .subcommand(
+            Command::new("shift-fingerprint")
+                .about(gettext("Shift the contents of a data-URI Shazam fingerprint in time, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("offset")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f32))
+                        .help(gettext("The offset to apply in seconds: a positive value inserts silence at the start, a negative value cuts the start."))
+                )
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to shift."))
+                )
+        )
+        .subcommand(
+            Command::new("trim-fingerprint")
+                .about(gettext("Remove the silent start and end of a data-URI Shazam fingerprint, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("fingerprint")
+                        .required(true)
+                        .help(gettext("The data-URI Shazam fingerprint to trim."))
+                )
+        )
+        .subcommand(
+            Command::new("concatenate-fingerprints")
+                .about(gettext("Join data-URI Shazam fingerprints one after the other, and print the resulting fingerprint to the standard output."))
+                .arg(
+                    Arg::new("fingerprints")
+                        .required(true)
+                        .num_args(2..)
+                        .help(gettext("The data-URI Shazam fingerprints to join, in order."))
+                )
+        )
+        .subcommand(
+            Command::new("compare-fingerprints")
+                .about(gettext("Compare two sound files or data-URI Shazam fingerprints offline, and print in JSON their similarity score, number of matching frequency peaks and time offset."))
+                .arg(
+                    Arg::new("frequency-tolerance")
+                        .short('t')
+                        .long("frequency-tolerance")
+                        .value_parser(clap::value_parser!(u32))
+                        .help(gettext("Maximal frequency difference between two matching peaks, in FFT bins of about 7.8 Hz (defaults to 1)"))
+                )
+                .arg(
+                    Arg::new("first_input")
+                        .required(true)
+                        .help(gettext("The first audio file or data-URI Shazam fingerprint to compare."))
+                )
+                .arg(
+                    Arg::new("second_input")
+                        .required(true)
+                        .help(gettext("The second audio file or data-URI Shazam fingerprint to compare."))
+                )
+        )
+        .subcommand(
+            Command::new("build-local-database")
+                .about(gettext("Fingerprint audio files and add them to a local database, allowing to recognize them later without network access."))
+                .arg(
+                    Arg::new("database")
+                        .required(true)
+                        .help(gettext("The local database file to create or update."))
+                )
+                .arg(
+                    Arg::new("input_paths")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files or directories to index."))
+                )
+        )
+        .subcommand(
+            Command::new("recognize-from-local-database")
+                .about(gettext("Recognize a sound file or a data-URI Shazam fingerprint against a local database, and print the matching songs in JSON to the standard output."))
+                .arg(
+                    Arg::new("database")
+                        .required(true)
+                        .help(gettext("The local database file to use."))
+                )
+                .arg(
+                    Arg::new("input")
+                        .required(true)
+                        .help(gettext("The audio file or data-URI Shazam fingerprint to recognize."))
+                )
+        )
+        .subcommand(
+            Command::new("tracklist")
+                .about(gettext("Recognize songs all along a long sound file (e.g. a DJ mix or a radio recording), and print a timestamped tracklist."))
+                .arg(
+                    Arg::new("step")
+                        .short('s')
+                        .long("step")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u32))
+                        .help(gettext("Interval between the starts of two recognized excerpts of the file, in seconds"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .default_value("shazam")
+                        .value_parser(["shazam", "fixtures", "command"])
+                        .help(gettext("Recognize songs against Shazam's servers, against responses recorded in JSON files (see --fixtures), or using a command (see --backend-command)"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .long("fixtures")
+                        .value_name("PATH")
+                        .required_if_eq("backend", "fixtures")
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn, for the fixtures backend"))
+                )
+                .arg(
+                    Arg::new("backend-command")
+                        .long("backend-command")
+                        .value_name("COMMAND")
+                        .required_if_eq("backend", "command")
+                        .help(gettext("A command receiving the signature URI as its last argument and printing a Shazam-like JSON response, for the command backend"))
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .value_name("URL")
+                        .help(gettext("Base URL of the recognition API, e.g. the one of a stand-in server (see serve-fixtures), rather than Shazam's (defaults to $SONGREC_RECOGNITION_ENDPOINT if set)"))
+                )
+                .arg(
+                    Arg::new("request-locale")
+                        .long("request-locale")
+                        .value_name("LOCALE")
+                        .help(gettext("Language and country to obtain song information in, e.g. de_DE (defaults to the locale of the system)"))
+                )
+                .arg(
+                    Arg::new("request-timezone")
+                        .long("request-timezone")
+                        .value_name("TIMEZONE")
+                        .help(gettext("Timezone to make recognition requests from, e.g. Europe/Berlin (defaults to the timezone of the system)"))
+                )
+                .arg(
+                    Arg::new("request-latitude")
+                        .long("request-latitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Latitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("request-longitude")
+                        .long("request-longitude")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(f64))
+                        .help(gettext("Longitude to make recognition requests from"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in JSON"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in the CSV format"))
+                )
+                .arg(
+                    Arg::new("input_file")
+                        .required(true)
+                        .help(gettext("The audio file to generate a tracklist for."))
+                )
+        )
+        .subcommand(
+            Command::new("serve-fixtures")
+                .about(gettext("Run a local server imitating Shazam's API, which answers with responses recorded in JSON files (use with --endpoint or $SONGREC_RECOGNITION_ENDPOINT)."))
+                .arg(
+                    Arg::new("port")
+                        .short('p')
+                        .long("port")
+                        .default_value("8080")
+                        .value_parser(clap::value_parser!(u32).range(1..=65535))
+                        .help(gettext("The local port to listen on"))
+                )
+                .arg(
+                    Arg::new("fixtures")
+                        .required(true)
+                        .help(gettext("A JSON response recorded from Shazam, or a directory of them to answer with in turn (cover images are served from the files at their URL paths within it)"))
+                )
+        )
     };
 }
 
@@ -305,7 +873,41 @@ macro_rules! app {
     };
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Read the --offset, --duration and --strategy options of the subcommands
+/// recognizing audio files.
+fn recognition_window_from_args(
+    subcommand_args: &ArgMatches,
+) -> Result<RecognitionWindow, Box<dyn Error>> {
+    Ok(RecognitionWindow {
+        offset: subcommand_args
+            .get_one::<u64>("offset")
+            .map(|offset| Duration::from_secs(*offset)),
+        duration: Duration::from_secs(*subcommand_args.get_one::<u64>("duration").unwrap()),
+        strategy: subcommand_args
+            .get_one::<String>("strategy")
+            .unwrap()
+            .parse::<WindowStrategy>()?,
+    })
+}
+
+fn main() -> ExitCode {
+    match songrec_main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}: {}", gettext("Error"), error);
+
+            // Use distinct exit codes for malformed fingerprints, so that
+            // scripts may tell them apart from other failures
+
+            match error.downcast_ref::<SignatureError>() {
+                Some(signature_error) => ExitCode::from(signature_error.exit_code()),
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn songrec_main() -> Result<(), Box<dyn Error>> {
     // Set up the translation/internationalization part
 
     let i18n_folder = setup_internationalization();
@@ -359,10 +961,9 @@ fn main() -> Result<(), Box<dyn Error>> {
             let session = soup::Session::new();
             session.set_timeout(20);
 
-            let input_file_string = subcommand_args
-                .get_one::<String>("input_file")
-                .unwrap()
-                .clone();
+            let input_file_string = subcommand_args.get_one::<String>("input_file").unwrap();
+
+            let signature = SignatureGenerator::make_signature_from_file(input_file_string)?;
 
             let main_loop = glib::MainLoop::new(None, false);
             let main_loop_inner = main_loop.clone();
@@ -370,13 +971,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                 println!(
                     "{}",
                     serde_json::to_string_pretty(
-                        &recognize_song_from_signature(
-                            &session,
-                            &SignatureGenerator::make_signature_from_file(&input_file_string)
-                                .unwrap()
-                        )
-                        .await
-                        .unwrap()
+                        &recognize_song_from_signature(&session, &signature)
+                            .await
+                            .unwrap()
                     )
                     .unwrap()
                 );
@@ -391,20 +988,23 @@ fn main() -> Result<(), Box<dyn Error>> {
 
             let input_file_string = subcommand_args.get_one::<String>("input_file").unwrap();
 
-            println!(
-                "{}",
-                SignatureGenerator::make_signature_from_file(input_file_string)?.encode_to_uri()?
-            );
+            // Print one fingerprint per excerpt with the "multi" strategy
+
+            for signature in SignatureGenerator::make_signatures_from_file(
+                input_file_string,
+                &recognition_window_from_args(subcommand_args)?,
+            )? {
+                println!("{}", signature.encode_to_uri()?);
+            }
         }
         Some("fingerprint-to-recognized-song") => {
             let subcommand_args = args
                 .subcommand_matches("fingerprint-to-recognized-song")
                 .unwrap();
 
-            let fingerprint_string = subcommand_args
-                .get_one::<String>("fingerprint")
-                .unwrap()
-                .clone();
+            let fingerprint_string = subcommand_args.get_one::<String>("fingerprint").unwrap();
+
+            let signature = DecodedSignature::decode_from_uri(fingerprint_string)?;
 
             let session = soup::Session::new();
             session.set_timeout(20);
@@ -415,12 +1015,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                 println!(
                     "{}",
                     serde_json::to_string_pretty(
-                        &recognize_song_from_signature(
-                            &session,
-                            &DecodedSignature::decode_from_uri(&fingerprint_string).unwrap()
-                        )
-                        .await
-                        .unwrap()
+                        &recognize_song_from_signature(&session, &signature)
+                            .await
+                            .unwrap()
                     )
                     .unwrap()
                 );
@@ -428,14 +1025,303 @@ fn main() -> Result<(), Box<dyn Error>> {
             });
             main_loop.run();
         }
+        Some("convert-fingerprint") => {
+            let subcommand_args = args.subcommand_matches("convert-fingerprint").unwrap();
+
+            let signature = DecodedSignature::decode_from_input(
+                subcommand_args.get_one::<String>("input").unwrap(),
+            )?;
+
+            match subcommand_args.get_one::<String>("to").unwrap().as_str() {
+                "uri" => {
+                    println!("{}", signature.encode_to_uri()?);
+                }
+                "toml" => {
+                    print!("{}", toml::to_string(&signature)?);
+                }
+                _ => {
+                    println!("{}", serde_json::to_string_pretty(&signature)?);
+                }
+            }
+        }
+        Some("inspect-fingerprint") => {
+            let subcommand_args = args.subcommand_matches("inspect-fingerprint").unwrap();
+
+            let inspection = SignatureInspection::from_input(
+                subcommand_args.get_one::<String>("input").unwrap(),
+            )?;
+
+            match subcommand_args
+                .get_one::<String>("format")
+                .unwrap()
+                .as_str()
+            {
+                "json" => {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&inspection.encode_to_json())?
+                    );
+                }
+                "csv" => {
+                    write_peaks_csv(
+                        inspection.signature.as_ref().map_err(Clone::clone)?,
+                        std::io::stdout(),
+                    )?;
+                }
+                _ => {
+                    inspection.print_report();
+                }
+            }
+
+            if let Some(plot_path) = subcommand_args.get_one::<String>("plot") {
+                render_constellation_plot(
+                    inspection.signature.as_ref().map_err(Clone::clone)?,
+                    Path::new(plot_path),
+                )?;
+            }
+        }
+        Some("slice-fingerprint") => {
+            let subcommand_args = args.subcommand_matches("slice-fingerprint").unwrap();
+
+            let signature = DecodedSignature::decode_from_uri(
+                subcommand_args.get_one::<String>("fingerprint").unwrap(),
+            )?;
+
+            let start_pass = signature
+                .seconds_to_fft_passes(*subcommand_args.get_one::<f32>("start").unwrap())
+                .clamp(0, u32::MAX as i64) as u32;
+            let end_pass = match subcommand_args.get_one::<f32>("end") {
+                Some(end) => signature
+                    .seconds_to_fft_passes(*end)
+                    .clamp(0, u32::MAX as i64) as u32,
+                None => u32::MAX,
+            };
+
+            println!("{}", signature.slice(start_pass, end_pass).encode_to_uri()?);
+        }
+        Some("shift-fingerprint") => {
+            let subcommand_args = args.subcommand_matches("shift-fingerprint").unwrap();
+
+            let signature = DecodedSignature::decode_from_uri(
+                subcommand_args.get_one::<String>("fingerprint").unwrap(),
+            )?;
+
+            let offset_passes =
+                signature.seconds_to_fft_passes(*subcommand_args.get_one::<f32>("offset").unwrap());
+
+            println!("{}", signature.time_shift(offset_passes).encode_to_uri()?);
+        }
+        Some("trim-fingerprint") => {
+            let subcommand_args = args.subcommand_matches("trim-fingerprint").unwrap();
+
+            let signature = DecodedSignature::decode_from_uri(
+                subcommand_args.get_one::<String>("fingerprint").unwrap(),
+            )?;
+
+            println!("{}", signature.trim().encode_to_uri()?);
+        }
+        Some("concatenate-fingerprints") => {
+            let subcommand_args = args.subcommand_matches("concatenate-fingerprints").unwrap();
+
+            let mut fingerprint_strings =
+                subcommand_args.get_many::<String>("fingerprints").unwrap();
+
+            let mut signature =
+                DecodedSignature::decode_from_uri(fingerprint_strings.next().unwrap())?;
+
+            for fingerprint_string in fingerprint_strings {
+                signature = signature
+                    .concatenate(&DecodedSignature::decode_from_uri(fingerprint_string)?)?;
+            }
+
+            println!("{}", signature.encode_to_uri()?);
+        }
+        Some("compare-fingerprints") => {
+            let subcommand_args = args.subcommand_matches("compare-fingerprints").unwrap();
+
+            // Whole audio files are fingerprinted, so that two recordings
+            // may be aligned wherever they overlap
+
+            let mut signatures: Vec<DecodedSignature> = vec![];
+
+            for input_name in ["first_input", "second_input"] {
+                let input_string = subcommand_args.get_one::<String>(input_name).unwrap();
+
+                signatures.push(if input_string.starts_with("data:") {
+                    DecodedSignature::decode_from_uri(input_string)?
+                } else {
+                    SignatureGenerator::make_full_signature_from_file(input_string)?
+                });
+            }
+
+            let similarity = match subcommand_args.get_one::<u32>("frequency-tolerance") {
+                Some(frequency_tolerance) => {
+                    signatures[0].similarity_with_tolerance(&signatures[1], *frequency_tolerance)?
+                }
+                None => signatures[0].similarity(&signatures[1])?,
+            };
+
+            println!("{}", serde_json::to_string_pretty(&similarity)?);
+        }
+        Some("build-local-database") => {
+            let subcommand_args = args.subcommand_matches("build-local-database").unwrap();
+
+            let database_path =
+                PathBuf::from(subcommand_args.get_one::<String>("database").unwrap());
+
+            let mut database = LocalDatabase::load_or_create(&database_path)?;
+
+            let mut files: Vec<PathBuf> = vec![];
+            for input_path in subcommand_args.get_many::<String>("input_paths").unwrap() {
+                collect_files(Path::new(input_path), &mut files);
+            }
+
+            for file in files {
+                match database.add_file(&file) {
+                    Ok(true) => {
+                        info!("{} {}", gettext("Indexed:"), file.display());
+                    }
+                    Ok(false) => {
+                        debug!("Already indexed: {}", file.display());
+                    }
+                    Err(error) => {
+                        warn!(
+                            "{} {}: {}",
+                            gettext("Could not index"),
+                            file.display(),
+                            error
+                        );
+                    }
+                }
+            }
+
+            database.save(&database_path)?;
+        }
+        Some("recognize-from-local-database") => {
+            let subcommand_args = args
+                .subcommand_matches("recognize-from-local-database")
+                .unwrap();
+
+            let database = LocalDatabase::load(Path::new(
+                subcommand_args.get_one::<String>("database").unwrap(),
+            ))?;
+
+            let input_string = subcommand_args.get_one::<String>("input").unwrap();
+
+            let signature = if input_string.starts_with("data:") {
+                DecodedSignature::decode_from_uri(input_string)?
+            } else {
+                SignatureGenerator::make_signature_from_file(input_string)?
+            };
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&database.find_matches(&signature))?
+            );
+        }
+        Some("tracklist") => {
+            let subcommand_args = args.subcommand_matches("tracklist").unwrap();
+            let step = *subcommand_args.get_one::<u32>("step").unwrap();
+            let request_interval = *subcommand_args.get_one::<u64>("request-interval").unwrap();
+            let enable_json = subcommand_args.get_flag("json");
+            let enable_csv = subcommand_args.get_flag("csv");
+
+            let mut preferences = Preferences::with_interval(request_interval);
+            preferences.recognition_backend = subcommand_args.get_one::<String>("backend").cloned();
+            preferences.recognition_fixtures_path =
+                subcommand_args.get_one::<String>("fixtures").cloned();
+            preferences.recognition_command = subcommand_args
+                .get_one::<String>("backend-command")
+                .cloned();
+            preferences.recognition_endpoint =
+                subcommand_args.get_one::<String>("endpoint").cloned();
+            preferences.request_locale =
+                subcommand_args.get_one::<String>("request-locale").cloned();
+            preferences.request_timezone = subcommand_args
+                .get_one::<String>("request-timezone")
+                .cloned();
+            preferences.request_latitude =
+                subcommand_args.get_one::<f64>("request-latitude").copied();
+            preferences.request_longitude =
+                subcommand_args.get_one::<f64>("request-longitude").copied();
+
+            let backend = get_recognition_backend(&preferences)?;
+
+            let input_file_string = subcommand_args.get_one::<String>("input_file").unwrap();
+
+            let windows = make_window_signatures(input_file_string, step)?;
+
+            let main_loop = glib::MainLoop::new(None, false);
+            let main_loop_inner = main_loop.clone();
+            glib::spawn_future_local(async move {
+                let tracklist = recognize_windows(
+                    backend.as_ref(),
+                    &windows,
+                    std::time::Duration::from_secs(request_interval),
+                )
+                .await;
+
+                if enable_json {
+                    println!("{}", serde_json::to_string_pretty(&tracklist).unwrap());
+                } else if enable_csv {
+                    let mut csv_writer = csv::Writer::from_writer(std::io::stdout());
+                    for entry in &tracklist {
+                        csv_writer.serialize(entry).unwrap();
+                    }
+                    csv_writer.flush().unwrap();
+                } else {
+                    for entry in &tracklist {
+                        println!(
+                            "{} - {}  {} - {}",
+                            format_timestamp(entry.start_secs),
+                            format_timestamp(entry.end_secs),
+                            entry.artist_name,
+                            entry.song_name
+                        );
+                    }
+                }
+                main_loop_inner.quit();
+            });
+            main_loop.run();
+        }
+        Some("serve-fixtures") => {
+            let subcommand_args = args.subcommand_matches("serve-fixtures").unwrap();
+            let port = *subcommand_args.get_one::<u32>("port").unwrap();
+            let fixtures_path = subcommand_args.get_one::<String>("fixtures").unwrap();
+
+            serve_fixtures(fixtures_path, port)?;
+        }
         Some("listen") => {
             let subcommand_args = args.subcommand_matches("listen").unwrap();
             let list_devices = subcommand_args.get_flag("list-devices");
             let audio_device = subcommand_args.get_one::<String>("audio-device").cloned();
             let request_interval = *subcommand_args.get_one::<u64>("request-interval").unwrap();
+            let min_quality_percent = *subcommand_args.get_one::<u64>("min-quality").unwrap();
+            let recognition_backend = subcommand_args
+                .get_one::<String>("backend")
+                .unwrap()
+                .clone();
+            let recognition_fixtures_path = subcommand_args.get_one::<String>("fixtures").cloned();
+            let recognition_command = subcommand_args
+                .get_one::<String>("backend-command")
+                .cloned();
+            let recognition_endpoint = subcommand_args.get_one::<String>("endpoint").cloned();
+            let cover_endpoint = subcommand_args.get_one::<String>("cover-endpoint").cloned();
+            let request_locale = subcommand_args.get_one::<String>("request-locale").cloned();
+            let request_timezone = subcommand_args
+                .get_one::<String>("request-timezone")
+                .cloned();
+            let request_latitude = subcommand_args.get_one::<f64>("request-latitude").copied();
+            let request_longitude = subcommand_args.get_one::<f64>("request-longitude").copied();
             let enable_mpris = !subcommand_args.get_flag("disable-mpris");
+            let detect_song_changes = subcommand_args.get_flag("detect-changes");
             let enable_json = subcommand_args.get_flag("json");
             let enable_csv = subcommand_args.get_flag("csv");
+            let print_lyrics = subcommand_args.get_flag("lyrics");
+            let raw_pcm_format = subcommand_args
+                .get_one::<String>("raw-pcm")
+                .map(|raw_pcm_format| raw_pcm_format.parse::<RawPcmFormat>())
+                .transpose()?;
 
             cli_main(CLIParameters {
                 enable_mpris,
@@ -443,7 +1329,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 recognize_once: false,
                 audio_device,
                 request_interval,
+                min_quality_percent,
+                detect_song_changes,
+                recognition_backend,
+                recognition_fixtures_path,
+                recognition_command,
+                recognition_endpoint,
+                cover_endpoint,
+                request_locale,
+                request_timezone,
+                request_latitude,
+                request_longitude,
                 input_file: None,
+                recognition_window: RecognitionWindow::default(),
+                raw_pcm_format,
                 output_type: if enable_json {
                     CLIOutputType::JSON
                 } else if enable_csv {
@@ -451,6 +1350,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 } else {
                     CLIOutputType::SongName
                 },
+                print_lyrics,
             })?;
         }
         Some("recognize") => {
@@ -458,9 +1358,32 @@ fn main() -> Result<(), Box<dyn Error>> {
             let list_devices = subcommand_args.get_flag("list-devices");
             let audio_device = subcommand_args.get_one::<String>("audio-device").cloned();
             let request_interval = *subcommand_args.get_one::<u64>("request-interval").unwrap();
+            let min_quality_percent = *subcommand_args.get_one::<u64>("min-quality").unwrap();
+            let recognition_backend = subcommand_args
+                .get_one::<String>("backend")
+                .unwrap()
+                .clone();
+            let recognition_fixtures_path = subcommand_args.get_one::<String>("fixtures").cloned();
+            let recognition_command = subcommand_args
+                .get_one::<String>("backend-command")
+                .cloned();
+            let recognition_endpoint = subcommand_args.get_one::<String>("endpoint").cloned();
+            let cover_endpoint = subcommand_args.get_one::<String>("cover-endpoint").cloned();
+            let request_locale = subcommand_args.get_one::<String>("request-locale").cloned();
+            let request_timezone = subcommand_args
+                .get_one::<String>("request-timezone")
+                .cloned();
+            let request_latitude = subcommand_args.get_one::<f64>("request-latitude").copied();
+            let request_longitude = subcommand_args.get_one::<f64>("request-longitude").copied();
             let input_file = subcommand_args.get_one::<String>("input_file").cloned();
+            let recognition_window = recognition_window_from_args(subcommand_args)?;
             let enable_json = subcommand_args.get_flag("json");
             let enable_csv = subcommand_args.get_flag("csv");
+            let print_lyrics = subcommand_args.get_flag("lyrics");
+            let raw_pcm_format = subcommand_args
+                .get_one::<String>("raw-pcm")
+                .map(|raw_pcm_format| raw_pcm_format.parse::<RawPcmFormat>())
+                .transpose()?;
 
             cli_main(CLIParameters {
                 enable_mpris: false,
@@ -468,7 +1391,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 recognize_once: true,
                 audio_device,
                 request_interval,
+                min_quality_percent,
+                detect_song_changes: false,
+                recognition_backend,
+                recognition_fixtures_path,
+                recognition_command,
+                recognition_endpoint,
+                cover_endpoint,
+                request_locale,
+                request_timezone,
+                request_latitude,
+                request_longitude,
                 input_file,
+                recognition_window,
+                raw_pcm_format,
 
                 output_type: if enable_json {
                     CLIOutputType::JSON
@@ -477,6 +1413,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 } else {
                     CLIOutputType::SongName
                 },
+                print_lyrics,
             })?;
         }
         Some("microphone-to-recognized-song") => {
@@ -486,6 +1423,23 @@ fn main() -> Result<(), Box<dyn Error>> {
             let list_devices = subcommand_args.get_flag("list-devices");
             let audio_device = subcommand_args.get_one::<String>("audio-device").cloned();
             let request_interval = *subcommand_args.get_one::<u64>("request-interval").unwrap();
+            let min_quality_percent = *subcommand_args.get_one::<u64>("min-quality").unwrap();
+            let recognition_backend = subcommand_args
+                .get_one::<String>("backend")
+                .unwrap()
+                .clone();
+            let recognition_fixtures_path = subcommand_args.get_one::<String>("fixtures").cloned();
+            let recognition_command = subcommand_args
+                .get_one::<String>("backend-command")
+                .cloned();
+            let recognition_endpoint = subcommand_args.get_one::<String>("endpoint").cloned();
+            let cover_endpoint = subcommand_args.get_one::<String>("cover-endpoint").cloned();
+            let request_locale = subcommand_args.get_one::<String>("request-locale").cloned();
+            let request_timezone = subcommand_args
+                .get_one::<String>("request-timezone")
+                .cloned();
+            let request_latitude = subcommand_args.get_one::<f64>("request-latitude").copied();
+            let request_longitude = subcommand_args.get_one::<f64>("request-longitude").copied();
 
             cli_main(CLIParameters {
                 enable_mpris: false,
@@ -493,8 +1447,22 @@ fn main() -> Result<(), Box<dyn Error>> {
                 recognize_once: true,
                 audio_device,
                 request_interval,
+                min_quality_percent,
+                detect_song_changes: false,
+                recognition_backend,
+                recognition_fixtures_path,
+                recognition_command,
+                recognition_endpoint,
+                cover_endpoint,
+                request_locale,
+                request_timezone,
+                request_latitude,
+                request_longitude,
                 input_file: None,
+                recognition_window: RecognitionWindow::default(),
+                raw_pcm_format: None,
                 output_type: CLIOutputType::JSON,
+                print_lyrics: false,
             })?;
         }
         #[cfg(feature = "gui")]
@@ -531,8 +1499,22 @@ fn main() -> Result<(), Box<dyn Error>> {
                 recognize_once: false,
                 audio_device: None,
                 request_interval: 10,
+                min_quality_percent: DEFAULT_MIN_QUALITY_PERCENT,
+                detect_song_changes: false,
+                recognition_backend: DEFAULT_RECOGNITION_BACKEND.to_string(),
+                recognition_fixtures_path: None,
+                recognition_command: None,
+                recognition_endpoint: None,
+                cover_endpoint: None,
+                request_locale: None,
+                request_timezone: None,
+                request_latitude: None,
+                request_longitude: None,
                 input_file: None,
+                recognition_window: RecognitionWindow::default(),
+                raw_pcm_format: None,
                 output_type: CLIOutputType::SongName,
+                print_lyrics: false,
             })?;
         }
         _ => unreachable!(),
@@ -1,92 +1,312 @@
 use log::{debug, error};
-use std::io::BufReader;
+use rodio::nz;
+use rodio::source::SeekError;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
-use std::process::Command;
+use std::process::{Child, ChildStdout, Command, Stdio};
 
-/// This function used to decode a file with FFMpeg, if it is installed on
-/// the system, in the case where Rodio can't decode the concerned format
-/// (for example with .WMA, .M4A, etc.).
-pub fn decode_with_ffmpeg(file_path: &str) -> Option<rodio::Decoder<BufReader<std::fs::File>>> {
-    // Find the path for FFMpeg, in the case where it is installed
+/// Number of lines of FFMpeg's error output kept for reporting errors.
+const MAX_STDERR_LINES: usize = 20;
 
-    let mut possible_ffmpeg_paths: Vec<&str> = vec!["ffmpeg", "ffmpeg.exe"];
+/// Find the path for FFMpeg, in the case where it is installed. This is
+/// only done once, as it involves spawning a subprocess for each
+/// candidate path.
+fn ffmpeg_path() -> Option<&'static str> {
+    static FFMPEG_PATH: OnceLock<Option<String>> = OnceLock::new();
 
-    let mut current_dir_ffmpeg_path = std::env::current_exe().unwrap();
-    current_dir_ffmpeg_path.pop();
-    current_dir_ffmpeg_path.push("ffmpeg.exe");
+    FFMPEG_PATH
+        .get_or_init(|| {
+            let mut possible_ffmpeg_paths: Vec<String> =
+                vec!["ffmpeg".to_string(), "ffmpeg.exe".to_string()];
 
-    possible_ffmpeg_paths.push(current_dir_ffmpeg_path.to_str().unwrap());
+            if let Ok(mut current_dir_ffmpeg_path) = std::env::current_exe() {
+                current_dir_ffmpeg_path.pop();
+                current_dir_ffmpeg_path.push("ffmpeg.exe");
 
-    let mut actual_ffmpeg_path: Option<&str> = None;
+                if let Some(path) = current_dir_ffmpeg_path.to_str() {
+                    possible_ffmpeg_paths.push(path.to_string());
+                }
+            }
 
-    for possible_path in possible_ffmpeg_paths {
-        // Use .output() to execute the subprocess testing for FFMpeg
-        // presence and correct execution, so that it does not pollute
-        // the standard or error output in any way
+            possible_ffmpeg_paths.into_iter().find(|possible_path| {
+                // Use .output() to execute the subprocess testing for FFMpeg
+                // presence and correct execution, so that it does not pollute
+                // the standard or error output in any way
 
-        let mut command = Command::new(possible_path);
-        let command = command.arg("-version");
+                let mut command = Command::new(possible_path);
+                let command = command.arg("-version");
 
-        #[cfg(windows)]
-        let command = command.creation_flags(0x00000008); // Set "CREATE_NO_WINDOW" on Windows
+                #[cfg(windows)]
+                let command = command.creation_flags(0x00000008); // Set "CREATE_NO_WINDOW" on Windows
 
-        if let Ok(process) = command.output()
-            && process.status.success()
-        {
-            actual_ffmpeg_path = Some(possible_path);
-            break;
-        }
+                matches!(command.output(), Ok(process) if process.status.success())
+            })
+        })
+        .as_deref()
+}
+
+/// Parse the duration of the input from a line of FFMpeg's error output,
+/// such as "  Duration: 00:03:25.47, start: 0.000000, bitrate: 320 kb/s".
+fn parse_duration_line(line: &str) -> Option<Duration> {
+    let timestamp = line.trim().strip_prefix("Duration: ")?.split(',').next()?;
+
+    let mut seconds: f64 = 0.0;
+    for component in timestamp.split(':') {
+        seconds = seconds * 60.0 + component.parse::<f64>().ok()?;
     }
 
-    // If FFMpeg is available, use it to convert the input file
-    // from whichever format to a .WAV (because Rodio has its
-    // decoding support limited to .WAV, .FLAC, .OGG, .MP3, which
-    // makes that .MP4/.AAC, .OPUS or .WMA are not supported
-    // by default)
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// A Rodio source reading 16 KHz mono samples decoded by a FFMpeg
+/// subprocess through a pipe, for the formats that Rodio can't decode
+/// (for example .WMA, .MP4 video files, etc.). Seeking respawns FFMpeg at
+/// the requested position.
+pub struct FFMpegSource {
+    file_path: PathBuf,
+    max_duration: Option<Duration>,
 
-    if let Some(ffmpeg_path) = actual_ffmpeg_path {
-        // Create a sink file for FFMpeg
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    stderr_thread: Option<JoinHandle<()>>,
+    stderr_lines: Arc<Mutex<Vec<String>>>,
+    reached_end: bool,
 
-        let sink_file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+    total_duration: Option<Duration>,
+}
+
+/// Decode a file with FFMpeg, starting at `start` and for at most
+/// `max_duration`, if it is installed on the system. Returns None if FFMpeg
+/// is not available.
+pub fn decode_with_ffmpeg(
+    file_path: &Path,
+    start: Duration,
+    max_duration: Option<Duration>,
+) -> Option<Result<FFMpegSource, Box<dyn Error>>> {
+    let ffmpeg_path = ffmpeg_path()?;
 
-        let sink_file_path = sink_file.into_temp_path();
+    Some(FFMpegSource::spawn(
+        ffmpeg_path,
+        file_path,
+        start,
+        max_duration,
+    ))
+}
 
-        // Try to convert the input video or audio file to a standard
-        // .WAV s16le PCM file using FFMpeg, and pass it to Rodio
-        // later in the case where it succeeded
+impl FFMpegSource {
+    fn spawn(
+        ffmpeg_path: &str,
+        file_path: &Path,
+        start: Duration,
+        max_duration: Option<Duration>,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Have FFMpeg output raw s16le 16 KHz mono samples to its standard
+        // output, so that nothing needs to be written to the disk and no
+        // further resampling is needed
 
         let mut command = Command::new(ffmpeg_path);
 
-        let command = command.args(["-y", "-i", file_path, sink_file_path.to_str().unwrap()]);
+        command.args(["-hide_banner", "-nostdin", "-nostats"]);
 
-        debug!("Spawning ffmpeg: {:?}", command);
+        if !start.is_zero() {
+            command.args(["-ss", &format!("{:.3}", start.as_secs_f64())]);
+        }
+
+        command.arg("-i").arg(file_path);
+
+        if let Some(max_duration) = max_duration {
+            command.args(["-t", &format!("{:.3}", max_duration.as_secs_f64())]);
+        }
+
+        command.args(["-vn", "-ac", "1", "-ar", "16000", "-f", "s16le", "-"]);
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         // Set "CREATE_NO_WINDOW" on Windows, see
         // https://stackoverflow.com/a/60958956/662399
         #[cfg(windows)]
-        let command = command.creation_flags(0x00000008);
-
-        match command.output() {
-            Ok(process) => {
-                if process.status.success() {
-                    return Some(
-                        rodio::Decoder::new(BufReader::new(
-                            std::fs::File::open(sink_file_path.to_str().unwrap()).unwrap(),
-                        ))
-                        .unwrap(),
-                    );
-                } else {
-                    error!("ffmpeg returned an error: {:?}", process.status);
+        command.creation_flags(0x00000008);
+
+        debug!("Spawning ffmpeg: {:?}", command);
+
+        let mut child = command.spawn()?;
+
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let stderr = BufReader::new(child.stderr.take().unwrap());
+
+        // Collect the error output from a separate thread, so that FFMpeg
+        // can't get blocked on a full pipe. The duration of the input is
+        // printed in the header of this output, before any sample gets
+        // decoded.
+
+        let stderr_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let stderr_lines_2 = stderr_lines.clone();
+
+        let (duration_tx, duration_rx) = std::sync::mpsc::channel::<Option<Duration>>();
+
+        let stderr_thread = std::thread::spawn(move || {
+            let mut duration_tx = Some(duration_tx);
+
+            for line in stderr.split(b'\n').map_while(Result::ok) {
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+
+                if let Some(tx) = &duration_tx {
+                    if let Some(duration) = parse_duration_line(&line) {
+                        tx.send(Some(duration)).ok();
+                        duration_tx = None;
+                    } else if line.starts_with("Output #0") || line.starts_with("Stream mapping") {
+                        tx.send(None).ok();
+                        duration_tx = None;
+                    }
+                }
+
+                let mut stderr_lines = stderr_lines_2.lock().unwrap();
+                if stderr_lines.len() >= MAX_STDERR_LINES {
+                    stderr_lines.remove(0);
                 }
+                stderr_lines.push(line);
             }
-            Err(err) => {
-                error!("Could not spawn ffmpeg: {:?}", err);
+
+            if let Some(tx) = duration_tx {
+                tx.send(None).ok();
+            }
+        });
+
+        let total_duration = duration_rx
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap_or(None);
+
+        let mut source = FFMpegSource {
+            file_path: file_path.to_path_buf(),
+            max_duration,
+            child,
+            stdout,
+            stderr_thread: Some(stderr_thread),
+            stderr_lines,
+            reached_end: false,
+            total_duration,
+        };
+
+        // Wait for the first decoded samples, so that a file that FFMpeg
+        // can't decode is reported right away
+
+        if source.stdout.fill_buf()?.is_empty() {
+            let status = source.child.wait()?;
+
+            if !status.success() {
+                return Err(source.error_output().into());
+            }
+        }
+
+        Ok(source)
+    }
+
+    /// Last lines of FFMpeg's error output, for reporting errors once it
+    /// has exited.
+    fn error_output(&mut self) -> String {
+        if let Some(stderr_thread) = self.stderr_thread.take() {
+            stderr_thread.join().ok();
+        }
+
+        format!(
+            "FFMpeg could not decode {}: {}",
+            self.file_path.display(),
+            self.stderr_lines.lock().unwrap().join("\n")
+        )
+    }
+}
+
+impl Drop for FFMpegSource {
+    fn drop(&mut self) {
+        // FFMpeg has not necessarily reached the end of the input yet
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+impl Iterator for FFMpegSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let mut sample = [0u8; 2];
+
+        match self.stdout.read_exact(&mut sample) {
+            Ok(()) => Some(i16::from_le_bytes(sample) as f32 / 32768.0),
+            Err(_) => {
+                if !self.reached_end
+                    && let Ok(status) = self.child.wait()
+                    && !status.success()
+                {
+                    error!("{}", self.error_output());
+                }
+                self.reached_end = true;
+                None
             }
         }
     }
+}
+
+impl rodio::Source for FFMpegSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        nz!(1)
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        nz!(16000)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let ffmpeg_path = ffmpeg_path().unwrap();
+
+        *self = FFMpegSource::spawn(ffmpeg_path, &self.file_path, pos, self.max_duration).map_err(
+            |error| {
+                SeekError::Other(Arc::from(Box::<dyn Error + Send + Sync>::from(
+                    error.to_string(),
+                )))
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_duration_line() {
+    assert_eq!(
+        parse_duration_line("  Duration: 00:03:25.47, start: 0.000000, bitrate: 320 kb/s"),
+        Some(Duration::from_secs_f64(205.47))
+    );
+    assert_eq!(
+        parse_duration_line("  Duration: 01:00:00.00, start: 0.000000, bitrate: 128 kb/s"),
+        Some(Duration::from_secs(3600))
+    );
 
-    None
+    // Streams of unknown duration, and other lines of the output
+    assert_eq!(
+        parse_duration_line("  Duration: N/A, start: 0.000000, bitrate: N/A"),
+        None
+    );
+    assert_eq!(
+        parse_duration_line("  Stream #0:0: Audio: wmav2, 44100 Hz, stereo, fltp, 128 kb/s"),
+        None
+    );
 }
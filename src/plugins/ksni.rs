@@ -1,5 +1,5 @@
 use crate::core::thread_messages::GUIMessage;
-use gettextrs::gettext;
+use crate::utils::internationalization::gettext;
 use image::GenericImageView;
 use ksni::TrayMethods;
 
@@ -0,0 +1,182 @@
+use audiopus::coder::Decoder as OpusDecoder;
+use audiopus::packet::Packet as OpusPacket;
+use audiopus::{Channels, MutSignals, SampleRate};
+use log::debug;
+use rodio::nz;
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::codecs::CODEC_TYPE_OPUS;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Opus frames last at most 120 ms, which is 1920 samples at 16 KHz mono.
+const MAX_FRAME_SIZE: usize = 1920;
+
+/// A Rodio source decoding the first Opus track of an Ogg or Matroska/WebM
+/// container. libopus is asked to directly output 16 KHz mono samples, so
+/// that no further resampling is needed.
+pub struct OpusSource {
+    format_reader: Box<dyn FormatReader>,
+    track_id: u32,
+    decoder: OpusDecoder,
+
+    /// Number of samples to discard at the start of the stream, as
+    /// specified by the "pre-skip" field of the Opus header.
+    samples_to_skip: usize,
+
+    decoded_samples: Vec<f32>,
+    decoded_samples_index: usize,
+}
+
+/// This function is used to decode Opus audio (.OPUS, .OGG, .WEBM or .MKV
+/// files), which Rodio's Symphonia backend can't decode. The container
+/// format is detected from the contents of the file rather than from its
+/// extension.
+pub fn decode_with_opus(file_path: &Path) -> Option<OpusSource> {
+    match OpusSource::new(file_path) {
+        Ok(source) => Some(source),
+        Err(error) => {
+            debug!(
+                "Could not decode {} as Opus: {}",
+                file_path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+impl OpusSource {
+    fn new(file_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let media_source_stream = MediaSourceStream::new(
+            Box::new(std::fs::File::open(file_path)?),
+            Default::default(),
+        );
+
+        let format_reader = symphonia::default::get_probe()
+            .format(
+                &Hint::new(),
+                media_source_stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+
+        let track = format_reader
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec == CODEC_TYPE_OPUS)
+            .ok_or_else(|| std::io::Error::other("No Opus track found"))?;
+
+        // The Opus header ("OpusHead" magic, version, channel count, then
+        // pre-skip as a little-endian 16-bit integer at 48 KHz) is passed
+        // by both the Ogg and Matroska demuxers as extra data
+
+        let pre_skip_48khz = match &track.codec_params.extra_data {
+            Some(header) if header.len() >= 12 && header.starts_with(b"OpusHead") => {
+                u16::from_le_bytes([header[10], header[11]]) as usize
+            }
+            _ => 0,
+        };
+
+        let track_id = track.id;
+
+        // Stereo streams are downmixed by libopus when asking for mono
+        // output
+
+        let decoder = OpusDecoder::new(SampleRate::Hz16000, Channels::Mono)?;
+
+        Ok(OpusSource {
+            format_reader,
+            track_id,
+            decoder,
+            samples_to_skip: pre_skip_48khz / 3,
+            decoded_samples: Vec::with_capacity(MAX_FRAME_SIZE),
+            decoded_samples_index: 0,
+        })
+    }
+
+    /// Decode the next Opus packet of the track. Returns false at the end
+    /// of the stream.
+    fn decode_next_packet(&mut self) -> bool {
+        loop {
+            let packet = match self.format_reader.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(error))
+                    if error.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return false;
+                }
+                Err(error) => {
+                    debug!("Could not read Opus packet: {}", error);
+                    return false;
+                }
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let Ok(opus_packet) = OpusPacket::try_from(&packet.data[..]) else {
+                continue;
+            };
+
+            self.decoded_samples.resize(MAX_FRAME_SIZE, 0.0);
+
+            let output = MutSignals::try_from(&mut self.decoded_samples[..]).unwrap();
+
+            match self.decoder.decode_float(Some(opus_packet), output, false) {
+                Ok(number_samples) => {
+                    self.decoded_samples.truncate(number_samples);
+
+                    let skipped_samples = self.samples_to_skip.min(number_samples);
+                    self.samples_to_skip -= skipped_samples;
+                    self.decoded_samples_index = skipped_samples;
+
+                    return true;
+                }
+                Err(error) => {
+                    debug!("Could not decode Opus packet: {}", error);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for OpusSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.decoded_samples_index >= self.decoded_samples.len() {
+            if !self.decode_next_packet() {
+                return None;
+            }
+        }
+
+        self.decoded_samples_index += 1;
+
+        Some(self.decoded_samples[self.decoded_samples_index - 1])
+    }
+}
+
+impl rodio::Source for OpusSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        nz!(1)
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        nz!(16000)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
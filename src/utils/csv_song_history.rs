@@ -7,7 +7,9 @@
 /// file, while antichronological order is used on the GUI list view.
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
+use crate::core::fingerprinting::shazam_response::{MatchDetails, StreamingLinks};
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Default)]
 pub struct SongHistoryRecord {
     pub song_name: String,
     #[serde(default)]
@@ -21,6 +23,72 @@ pub struct SongHistoryRecord {
     #[serde(default)]
     pub genre: Option<String>,
     pub recognition_date: String,
+
+    // How the recording matched the song, formatted as decimal numbers
+    #[serde(default)]
+    pub offset_secs: Option<String>,
+    #[serde(default)]
+    pub time_skew: Option<String>,
+    #[serde(default)]
+    pub frequency_skew: Option<String>,
+    #[serde(default)]
+    pub confidence: Option<String>,
+
+    // Kept so that the lyrics remain available without network access
+    #[serde(default)]
+    pub lyrics: Option<String>,
+
+    // Identifiers of the track and links to it on other services
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub shazam_link: Option<String>,
+    #[serde(default)]
+    pub apple_music_link: Option<String>,
+    #[serde(default)]
+    pub spotify_link: Option<String>,
+    #[serde(default)]
+    pub deezer_link: Option<String>,
+}
+
+impl SongHistoryRecord {
+    pub fn with_lyrics(self, lyrics: Option<&[String]>) -> Self {
+        SongHistoryRecord {
+            lyrics: lyrics.map(|lines| lines.join("\n")),
+            ..self
+        }
+    }
+
+    pub fn with_streaming_links(self, isrc: Option<String>, links: StreamingLinks) -> Self {
+        SongHistoryRecord {
+            isrc,
+            shazam_link: links.shazam,
+            apple_music_link: links.apple_music,
+            spotify_link: links.spotify,
+            deezer_link: links.deezer,
+            ..self
+        }
+    }
+
+    pub fn with_match_details(self, match_details: Option<MatchDetails>) -> Self {
+        let Some(match_details) = match_details else {
+            return self;
+        };
+
+        SongHistoryRecord {
+            offset_secs: match_details
+                .offset_secs
+                .map(|offset_secs| format!("{:.3}", offset_secs)),
+            time_skew: match_details
+                .time_skew
+                .map(|time_skew| format!("{:.6}", time_skew)),
+            frequency_skew: match_details
+                .frequency_skew
+                .map(|frequency_skew| format!("{:.6}", frequency_skew)),
+            confidence: Some(format!("{:.2}", match_details.confidence)),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
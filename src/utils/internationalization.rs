@@ -1,8 +1,26 @@
+#[cfg(feature = "i18n")]
 use gettextrs::{LocaleCategory, bind_textdomain_codeset, bindtextdomain, setlocale, textdomain};
+#[cfg(feature = "i18n")]
 use log::warn;
 use std::path::PathBuf;
 
+#[cfg(feature = "i18n")]
+pub use gettextrs::gettext;
+
+/// Without the "i18n" feature, messages are not translated and the
+/// gettext library is not needed.
+#[cfg(not(feature = "i18n"))]
+pub fn gettext<T: Into<String>>(msgid: T) -> String {
+    msgid.into()
+}
+
+#[cfg(not(feature = "i18n"))]
+pub fn setup_internationalization() -> Option<PathBuf> {
+    None
+}
+
 /// Set up the translation/internationalization part
+#[cfg(feature = "i18n")]
 pub fn setup_internationalization() -> Option<PathBuf> {
     // First, check for a "translations" directory in the
     // same directory as the current binary
@@ -16,7 +16,7 @@ cd "$(dirname "$0")/.."
 
 xgettext --c++ -kgettext --from-code utf-8 -o translations/songrec.pot.new \
     src/*.rs src/core/audio_controllers/*.rs src/core/*.rs \
-    src/core/fingerprinting/*.rs src/gui/*.rs src/gui/*/*.rs src/plugins/*.rs \
+    src/core/fingerprinting/*.rs src/core/recognition_backends/*.rs src/gui/*.rs src/gui/*/*.rs src/plugins/*.rs \
     src/utils/*.rs \
 
 xgettext --join-existing -L desktop -o translations/songrec.pot.new \